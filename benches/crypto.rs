@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use id_contact_jwt::{decrypt_and_verify_auth_result, sign_and_encrypt_auth_result};
+use id_contact_proto::{AuthResult, StartCommRequest};
+use josekit::{
+    jwe::{JweDecrypter, JweEncrypter, ECDH_ES, RSA_OAEP},
+    jwk::alg::ec::EcCurve,
+    jws::{JwsSigner, JwsVerifier, ES256, RS256},
+};
+
+struct Keys {
+    signer: Box<dyn JwsSigner>,
+    verifier: Box<dyn JwsVerifier>,
+    encrypter: Box<dyn JweEncrypter>,
+    decrypter: Box<dyn JweDecrypter>,
+}
+
+fn rsa_keys() -> Keys {
+    let sign_key = RS256.generate_key_pair(2048).unwrap();
+    let enc_key = RSA_OAEP.generate_key_pair(2048).unwrap();
+    Keys {
+        signer: Box::new(
+            RS256
+                .signer_from_der(sign_key.to_der_private_key())
+                .unwrap(),
+        ),
+        verifier: Box::new(
+            RS256
+                .verifier_from_der(sign_key.to_der_public_key())
+                .unwrap(),
+        ),
+        encrypter: Box::new(
+            RSA_OAEP
+                .encrypter_from_der(enc_key.to_der_public_key())
+                .unwrap(),
+        ),
+        decrypter: Box::new(
+            RSA_OAEP
+                .decrypter_from_der(enc_key.to_der_private_key())
+                .unwrap(),
+        ),
+    }
+}
+
+fn ec_keys() -> Keys {
+    let sign_key = ES256.generate_key_pair().unwrap();
+    let enc_key = ECDH_ES.generate_ec_key_pair(EcCurve::P256).unwrap();
+    Keys {
+        signer: Box::new(
+            ES256
+                .signer_from_der(sign_key.to_der_private_key())
+                .unwrap(),
+        ),
+        verifier: Box::new(
+            ES256
+                .verifier_from_der(sign_key.to_der_public_key())
+                .unwrap(),
+        ),
+        encrypter: Box::new(
+            ECDH_ES
+                .encrypter_from_der(enc_key.to_der_public_key())
+                .unwrap(),
+        ),
+        decrypter: Box::new(
+            ECDH_ES
+                .decrypter_from_der(enc_key.to_der_private_key())
+                .unwrap(),
+        ),
+    }
+}
+
+fn auth_result(attribute_count: usize) -> AuthResult {
+    let attributes: HashMap<String, String> = (0..attribute_count)
+        .map(|i| (format!("attribute{}", i), format!("value{}", i)))
+        .collect();
+    serde_json::from_value(serde_json::json!({
+        "status": "succes",
+        "attributes": attributes,
+    }))
+    .unwrap()
+}
+
+const ATTRIBUTE_COUNTS: &[usize] = &[1, 10, 100];
+
+fn bench_crypto(c: &mut Criterion) {
+    for (key_type, keys) in &[("RSA", rsa_keys()), ("EC", ec_keys())] {
+        let mut group = c.benchmark_group(format!("sign_and_encrypt_auth_result/{}", key_type));
+        for count in ATTRIBUTE_COUNTS {
+            let auth_result = auth_result(*count);
+            group.bench_with_input(BenchmarkId::from_parameter(count), &auth_result, |b, r| {
+                b.iter(|| {
+                    sign_and_encrypt_auth_result(
+                        black_box(r),
+                        keys.signer.as_ref(),
+                        keys.encrypter.as_ref(),
+                    )
+                    .unwrap()
+                })
+            });
+        }
+        group.finish();
+
+        let mut group = c.benchmark_group(format!("decrypt_and_verify_auth_result/{}", key_type));
+        for count in ATTRIBUTE_COUNTS {
+            let jwe = sign_and_encrypt_auth_result(
+                &auth_result(*count),
+                keys.signer.as_ref(),
+                keys.encrypter.as_ref(),
+            )
+            .unwrap();
+            group.bench_with_input(BenchmarkId::from_parameter(count), &jwe, |b, jwe| {
+                b.iter(|| {
+                    decrypt_and_verify_auth_result(
+                        black_box(jwe),
+                        keys.verifier.as_ref(),
+                        keys.decrypter.as_ref(),
+                    )
+                    .unwrap()
+                })
+            });
+        }
+        group.finish();
+    }
+}
+
+fn bench_serde(c: &mut Criterion) {
+    let keys = rsa_keys();
+    let jwe = sign_and_encrypt_auth_result(
+        &auth_result(10),
+        keys.signer.as_ref(),
+        keys.encrypter.as_ref(),
+    )
+    .unwrap();
+    let with_auth_result = serde_json::json!({
+        "purpose": "report_move",
+        "auth_result": jwe,
+    })
+    .to_string();
+    let without_auth_result = serde_json::json!({ "purpose": "report_move" }).to_string();
+
+    let mut group = c.benchmark_group("StartCommRequest deserialization");
+    group.bench_function("without auth_result", |b| {
+        b.iter(|| {
+            serde_json::from_str::<StartCommRequest>(black_box(&without_auth_result)).unwrap()
+        })
+    });
+    group.bench_function("with auth_result", |b| {
+        b.iter(|| serde_json::from_str::<StartCommRequest>(black_box(&with_auth_result)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_crypto, bench_serde);
+criterion_main!(benches);
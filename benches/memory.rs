@@ -0,0 +1,205 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use criterion::{
+    black_box, criterion_group, criterion_main,
+    measurement::{Measurement, ValueFormatter},
+    Criterion, Throughput,
+};
+use id_contact_jwt::{decrypt_and_verify_auth_result, sign_and_encrypt_auth_result};
+use id_contact_proto::{AuthResult, StartCommRequest};
+use josekit::{
+    jwe::{JweDecrypter, JweEncrypter, ECDH_ES},
+    jwk::alg::ec::EcCurve,
+    jws::{JwsSigner, JwsVerifier, ES256},
+};
+
+// Counts every byte allocated, with reallocations counted in full, so
+// benchmarks can report allocations instead of time
+struct CountingAllocator;
+
+static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_add(new_size as u64, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+struct AllocatedBytes;
+
+impl Measurement for AllocatedBytes {
+    type Intermediate = u64;
+    type Value = u64;
+
+    fn start(&self) -> u64 {
+        ALLOCATED.load(Ordering::SeqCst)
+    }
+
+    fn end(&self, start: u64) -> u64 {
+        ALLOCATED.load(Ordering::SeqCst) - start
+    }
+
+    fn add(&self, a: &u64, b: &u64) -> u64 {
+        a + b
+    }
+
+    fn zero(&self) -> u64 {
+        0
+    }
+
+    fn to_f64(&self, value: &u64) -> f64 {
+        *value as f64
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &BytesFormatter
+    }
+}
+
+struct BytesFormatter;
+
+impl BytesFormatter {
+    fn scale(typical_value: f64, values: &mut [f64]) -> &'static str {
+        let (factor, unit) = if typical_value < 1024.0 {
+            (1.0, "B")
+        } else if typical_value < 1024.0 * 1024.0 {
+            (1024.0, "KiB")
+        } else {
+            (1024.0 * 1024.0, "MiB")
+        };
+        for value in values {
+            *value /= factor;
+        }
+        unit
+    }
+}
+
+impl ValueFormatter for BytesFormatter {
+    fn scale_values(&self, typical_value: f64, values: &mut [f64]) -> &'static str {
+        BytesFormatter::scale(typical_value, values)
+    }
+
+    // Allocated bytes per byte of token
+    fn scale_throughputs(
+        &self,
+        _typical_value: f64,
+        throughput: &Throughput,
+        values: &mut [f64],
+    ) -> &'static str {
+        let bytes = match throughput {
+            Throughput::Bytes(bytes) => *bytes as f64,
+            Throughput::Elements(elements) => *elements as f64,
+        };
+        for value in values {
+            *value /= bytes;
+        }
+        "B/B"
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        "B"
+    }
+}
+
+struct Keys {
+    signer: Box<dyn JwsSigner>,
+    verifier: Box<dyn JwsVerifier>,
+    encrypter: Box<dyn JweEncrypter>,
+    decrypter: Box<dyn JweDecrypter>,
+}
+
+fn ec_keys() -> Keys {
+    let sign_key = ES256.generate_key_pair().unwrap();
+    let enc_key = ECDH_ES.generate_ec_key_pair(EcCurve::P256).unwrap();
+    Keys {
+        signer: Box::new(
+            ES256
+                .signer_from_der(sign_key.to_der_private_key())
+                .unwrap(),
+        ),
+        verifier: Box::new(
+            ES256
+                .verifier_from_der(sign_key.to_der_public_key())
+                .unwrap(),
+        ),
+        encrypter: Box::new(
+            ECDH_ES
+                .encrypter_from_der(enc_key.to_der_public_key())
+                .unwrap(),
+        ),
+        decrypter: Box::new(
+            ECDH_ES
+                .decrypter_from_der(enc_key.to_der_private_key())
+                .unwrap(),
+        ),
+    }
+}
+
+// Signing and encrypting base64 encodes the attributes twice, growing them by
+// about 16/9, so this makes a token of a little over 1 MB
+const ATTRIBUTE_COUNT: usize = 600;
+const VALUE_SIZE: usize = 1000;
+
+fn large_token(keys: &Keys) -> String {
+    let attributes: HashMap<String, String> = (0..ATTRIBUTE_COUNT)
+        .map(|i| (format!("attribute{}", i), "x".repeat(VALUE_SIZE)))
+        .collect();
+    let auth_result: AuthResult = serde_json::from_value(serde_json::json!({
+        "status": "succes",
+        "attributes": attributes,
+    }))
+    .unwrap();
+    sign_and_encrypt_auth_result(&auth_result, keys.signer.as_ref(), keys.encrypter.as_ref())
+        .unwrap()
+}
+
+fn bench_memory(c: &mut Criterion<AllocatedBytes>) {
+    let keys = ec_keys();
+    let jwe = large_token(&keys);
+    let request = serde_json::json!({
+        "purpose": "report_move",
+        "auth_result": jwe,
+    })
+    .to_string();
+
+    let mut group = c.benchmark_group("allocations for 1 MB tokens");
+    group.throughput(Throughput::Bytes(jwe.len() as u64));
+    group.bench_function("decrypt_and_verify_auth_result", |b| {
+        b.iter(|| {
+            decrypt_and_verify_auth_result(
+                black_box(&jwe),
+                keys.verifier.as_ref(),
+                keys.decrypter.as_ref(),
+            )
+            .unwrap()
+        })
+    });
+    group.bench_function("StartCommRequest deserialization", |b| {
+        b.iter(|| serde_json::from_str::<StartCommRequest>(black_box(&request)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().with_measurement(AllocatedBytes);
+    targets = bench_memory
+}
+criterion_main!(benches);
@@ -0,0 +1,103 @@
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome},
+    Request,
+};
+use serde::Deserialize;
+
+use crate::config::Config;
+
+// Ordered, each role includes the permissions of the ones before it
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    Operator,
+    Admin,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AdminToken {
+    pub token: String,
+    pub role: Role,
+}
+
+// Checks the value of an Authorization header, also for use outside of rocket
+pub fn check(config: &Config, authorization: Option<&str>, required: Role) -> Result<(), Status> {
+    // Without tokens or login configured the admin api stays open, as before
+    if config.admin_tokens().is_empty() && config.admin_oidc().is_none() {
+        return Ok(());
+    }
+
+    let token = authorization.and_then(|value| value.strip_prefix("Bearer "));
+    let role = token.and_then(|token| {
+        config
+            .admin_tokens()
+            .iter()
+            .find(|admin_token| admin_token.token == token)
+            .map(|admin_token| admin_token.role)
+    });
+
+    match role {
+        Some(role) if role >= required => Ok(()),
+        Some(_) => Err(Status::Forbidden),
+        None => Err(Status::Unauthorized),
+    }
+}
+
+fn authorize(request: &Request<'_>, required: Role) -> Outcome<(), ()> {
+    let config = match crate::reload::current(request) {
+        Some(config) => config,
+        None => return Outcome::Failure((Status::InternalServerError, ())),
+    };
+
+    // Browsers logged in through OpenID Connect carry a cookie instead
+    if config.admin_oidc().is_some() {
+        match crate::oidc::login_role(request) {
+            Some(role) if role >= required => return Outcome::Success(()),
+            Some(_) => return Outcome::Failure((Status::Forbidden, ())),
+            None => {}
+        }
+    }
+
+    match check(config, request.headers().get_one("Authorization"), required) {
+        Ok(()) => Outcome::Success(()),
+        Err(status) => Outcome::Failure((status, ())),
+    }
+}
+
+// Can read state
+pub struct Viewer;
+
+// Can additionally change behaviour, such as injecting faults
+pub struct Operator;
+
+// Can additionally purge data
+pub struct Admin;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Viewer {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        authorize(request, Role::Viewer).map(|_| Viewer)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Operator {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        authorize(request, Role::Operator).map(|_| Operator)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        authorize(request, Role::Admin).map(|_| Admin)
+    }
+}
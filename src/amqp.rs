@@ -0,0 +1,121 @@
+use id_contact_proto::StartCommRequest;
+use lapin::{
+    options::{
+        BasicAckOptions, BasicConsumeOptions, BasicNackOptions, BasicPublishOptions,
+        QueueDeclareOptions,
+    },
+    types::FieldTable,
+    BasicProperties, Connection, ConnectionProperties,
+};
+use rocket::{fairing::AdHoc, futures::StreamExt, Build, Rocket};
+use tokio_amqp::LapinTokioExt;
+use tracing::{error, info, warn};
+
+use crate::{config::Config, reload::LiveConfig, session::SessionStore, Error};
+
+fn handle_message(data: &[u8], config: &Config, sessions: &SessionStore) -> Result<Vec<u8>, Error> {
+    let request = serde_json::from_slice::<StartCommRequest>(data)?;
+    info!("Received AMQP communication request {:?}", request);
+
+    let response = crate::start_communication(&request, config, sessions)?;
+    Ok(serde_json::to_vec(&response)?)
+}
+
+async fn listen(live: LiveConfig, sessions: SessionStore) -> Result<(), lapin::Error> {
+    // Connection settings are read once, requests are handled with
+    // whatever configuration is current when they arrive
+    let config = live.current();
+    let amqp = match config.amqp() {
+        Some(amqp) => amqp,
+        None => return Ok(()),
+    };
+
+    let connection =
+        Connection::connect(&amqp.uri, ConnectionProperties::default().with_tokio()).await?;
+    let channel = connection.create_channel().await?;
+    for queue in &[&amqp.request_queue, &amqp.reply_queue] {
+        channel
+            .queue_declare(queue, QueueDeclareOptions::default(), FieldTable::default())
+            .await?;
+    }
+
+    let mut consumer = channel
+        .basic_consume(
+            &amqp.request_queue,
+            "comm-test",
+            BasicConsumeOptions::default(),
+            FieldTable::default(),
+        )
+        .await?;
+
+    info!("Listening for AMQP requests on {}", amqp.request_queue);
+    while let Some(delivery) = consumer.next().await {
+        let (channel, delivery) = delivery?;
+        match handle_message(&delivery.data, &live.current(), &sessions) {
+            Ok(response) => {
+                // Follow the usual RPC conventions when the sender asks for them
+                let reply_queue = delivery
+                    .properties
+                    .reply_to()
+                    .as_ref()
+                    .map(|queue| queue.as_str())
+                    .unwrap_or(&amqp.reply_queue);
+                let mut properties =
+                    BasicProperties::default().with_content_type("application/json".into());
+                if let Some(correlation_id) = delivery.properties.correlation_id() {
+                    properties = properties.with_correlation_id(correlation_id.clone());
+                }
+
+                channel
+                    .basic_publish(
+                        "",
+                        reply_queue,
+                        BasicPublishOptions::default(),
+                        response,
+                        properties,
+                    )
+                    .await?;
+                channel
+                    .basic_ack(delivery.delivery_tag, BasicAckOptions::default())
+                    .await?;
+            }
+            Err(e) => {
+                warn!("Failed to handle AMQP request: {}", e);
+                channel
+                    .basic_nack(
+                        delivery.delivery_tag,
+                        BasicNackOptions {
+                            requeue: false,
+                            ..BasicNackOptions::default()
+                        },
+                    )
+                    .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn attach(base: Rocket<Build>) -> Rocket<Build> {
+    base.attach(AdHoc::on_liftoff("AMQP listener", |rocket| {
+        Box::pin(async move {
+            let config = match rocket.state::<LiveConfig>() {
+                Some(config) => config.clone(),
+                None => return,
+            };
+            let sessions = match rocket.state::<SessionStore>() {
+                Some(sessions) => sessions.clone(),
+                None => return,
+            };
+
+            if config.current().amqp().is_some() {
+                rocket::tokio::spawn(async move {
+                    if let Err(e) = listen(config, sessions).await {
+                        error!("AMQP listener failed: {}", e);
+                    }
+                });
+            }
+        })
+    }))
+}
@@ -0,0 +1,77 @@
+use std::{collections::HashMap, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Config, keys, Error};
+
+// Levels of assurance as defined by eIDAS, ordered from low to high
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum AssuranceLevel {
+    Low,
+    Substantial,
+    High,
+}
+
+impl Display for AssuranceLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AssuranceLevel::Low => "low",
+            AssuranceLevel::Substantial => "substantial",
+            AssuranceLevel::High => "high",
+        })
+    }
+}
+
+// Prefix of the SAML authentication context classes of the eIDAS levels
+const EIDAS_LOA_PREFIX: &str = "http://eidas.europa.eu/LoA/";
+
+fn default_claim() -> String {
+    "loa".to_string()
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AssuranceConfig {
+    // Claim of the auth result token carrying the level
+    #[serde(default = "default_claim")]
+    claim: String,
+    // Level assumed for auth results without the claim
+    default_level: Option<AssuranceLevel>,
+    // Minimum level per purpose
+    #[serde(default)]
+    required: HashMap<String, AssuranceLevel>,
+}
+
+impl AssuranceConfig {
+    // Only meaningful for tokens that already passed verification, as the
+    // claims are read without checking the signature again
+    pub fn level(&self, token: &str, config: &Config) -> Option<AssuranceLevel> {
+        let kid = keys::jwe_key_id(token);
+        config
+            .decrypters(kid.as_deref())
+            .into_iter()
+            .find_map(|decrypter| keys::unverified_claims(token, decrypter))
+            .and_then(|claims| serde_json::from_value(claims.get(&self.claim)?.clone()).ok())
+            .or(self.default_level)
+    }
+
+    // Level of a SAML assertion, from its eIDAS authentication context class
+    pub fn saml_level(&self, authn_context: Option<&str>) -> Option<AssuranceLevel> {
+        let level = authn_context.and_then(|class| class.strip_prefix(EIDAS_LOA_PREFIX));
+        match level {
+            Some("low") => Some(AssuranceLevel::Low),
+            Some("substantial") => Some(AssuranceLevel::Substantial),
+            Some("high") => Some(AssuranceLevel::High),
+            _ => self.default_level,
+        }
+    }
+
+    pub fn check(&self, purpose: &str, level: Option<AssuranceLevel>) -> Result<(), Error> {
+        match self.required.get(purpose) {
+            Some(&required) if level.map_or(true, |level| level < required) => {
+                Err(Error::InsufficientAssurance(required))
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::{collections::HashMap, convert::Infallible, sync::Mutex};
+
+use id_contact_proto::AuthResult;
+use rocket::{
+    get,
+    request::{FromRequest, Outcome},
+    serde::json::Json,
+    Request, State,
+};
+
+use crate::{
+    access::Viewer,
+    session::{generate_id, SessionStore},
+};
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+// Auth results delivered via attr_url, keyed by request id. Results
+// belonging to a session are kept in the session store instead.
+#[derive(Default)]
+pub struct AuthResultStore {
+    results: Mutex<HashMap<String, AuthResult>>,
+}
+
+impl AuthResultStore {
+    pub fn insert(&self, id: &str, auth_result: AuthResult) {
+        self.results
+            .lock()
+            .unwrap()
+            .insert(id.to_string(), auth_result);
+    }
+
+    pub fn get(&self, id: &str) -> Option<AuthResult> {
+        self.results.lock().unwrap().get(id).cloned()
+    }
+}
+
+// Id under which a delivery is stored, taken from the request when given
+pub struct RequestId(pub String);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RequestId {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(RequestId(
+            request
+                .headers()
+                .get_one(REQUEST_ID_HEADER)
+                .map(|id| id.to_string())
+                .unwrap_or_else(generate_id),
+        ))
+    }
+}
+
+#[get("/internal/auth_results/<id>")]
+pub fn get(
+    _viewer: Viewer,
+    id: String,
+    sessions: &State<SessionStore>,
+    auth_results: &State<AuthResultStore>,
+) -> Option<Json<AuthResult>> {
+    sessions
+        .get(&id)
+        .and_then(|session| session.auth_result().cloned())
+        .or_else(|| auth_results.get(&id))
+        .map(Json)
+}
@@ -0,0 +1,55 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use rocket::{
+    http::{ContentType, Status},
+    request::Request,
+    response::{self, Responder, Response},
+};
+use serde::Serialize;
+
+// JSON body served with an ETag and Cache-Control, answering requests that
+// already hold the current version with 304 Not Modified, so polling
+// clients don't fetch the same document over and over
+pub struct Cached {
+    body: String,
+    max_age: u64,
+}
+
+impl Cached {
+    pub fn json(value: &impl Serialize, max_age: u64) -> Cached {
+        Cached {
+            body: serde_json::to_string(value).unwrap_or_default(),
+            max_age,
+        }
+    }
+
+    fn etag(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.body.hash(&mut hasher);
+        format!("\"{:016x}\"", hasher.finish())
+    }
+}
+
+impl<'r> Responder<'r, 'static> for Cached {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let etag = self.etag();
+        let cache_control = format!("public, max-age={}", self.max_age);
+        let matches = request
+            .headers()
+            .get("If-None-Match")
+            .flat_map(|value| value.split(','))
+            .any(|candidate| candidate.trim() == etag || candidate.trim() == "*");
+
+        let mut response = if matches {
+            Response::build().status(Status::NotModified).finalize()
+        } else {
+            (ContentType::JSON, self.body).respond_to(request)?
+        };
+        response.set_raw_header("ETag", etag);
+        response.set_raw_header("Cache-Control", cache_control);
+        Ok(response)
+    }
+}
@@ -0,0 +1,100 @@
+use std::convert::Infallible;
+
+use rocket::{
+    fairing::AdHoc,
+    get,
+    request::{FromRequest, Outcome},
+    serde::json::Json,
+    Build, Orbit, Request, Rocket,
+};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::{config::Config, reload::LiveConfig};
+
+#[derive(Serialize)]
+pub struct RouteDescription {
+    method: String,
+    uri: String,
+}
+
+#[derive(Serialize)]
+pub struct Capabilities {
+    version: &'static str,
+    features: Vec<&'static str>,
+    routes: Vec<RouteDescription>,
+    channels: Vec<&'static str>,
+    decryption_algorithm: String,
+    signature_algorithm: String,
+    uses_attr_url: bool,
+}
+
+fn enabled_features() -> Vec<&'static str> {
+    let mut features = vec![];
+    if cfg!(feature = "grpc") {
+        features.push("grpc");
+    }
+    if cfg!(feature = "amqp") {
+        features.push("amqp");
+    }
+    if cfg!(feature = "nats") {
+        features.push("nats");
+    }
+    if cfg!(feature = "outbox") {
+        features.push("outbox");
+    }
+    if cfg!(feature = "kafka") {
+        features.push("kafka");
+    }
+    if cfg!(feature = "ws") {
+        features.push("ws");
+    }
+    features
+}
+
+fn capabilities(rocket: &Rocket<Orbit>, config: &Config) -> Capabilities {
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        features: enabled_features(),
+        routes: rocket
+            .routes()
+            .map(|route| RouteDescription {
+                method: route.method.to_string(),
+                uri: route.uri.to_string(),
+            })
+            .collect(),
+        channels: config.channels(),
+        decryption_algorithm: config.decrypter().algorithm().name().to_string(),
+        signature_algorithm: config.validator().algorithm().name().to_string(),
+        uses_attr_url: config.use_attr_url(),
+    }
+}
+
+pub struct Launched<'r>(&'r Rocket<Orbit>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Launched<'r> {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(Launched(request.rocket()))
+    }
+}
+
+#[get("/capabilities")]
+pub fn get(rocket: Launched<'_>, config: &Config) -> Json<Capabilities> {
+    Json(capabilities(rocket.0, config))
+}
+
+pub fn attach(base: Rocket<Build>) -> Rocket<Build> {
+    base.attach(AdHoc::on_liftoff("Capability report", |rocket| {
+        Box::pin(async move {
+            if let Some(config) = rocket.state::<LiveConfig>().map(LiveConfig::current) {
+                match serde_json::to_string_pretty(&capabilities(rocket, &config)) {
+                    Ok(report) => info!("Capabilities: {}", report),
+                    Err(e) => warn!("Failed to generate capability report: {}", e),
+                }
+            }
+        })
+    }))
+}
@@ -0,0 +1,105 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use id_contact_proto::AuthResult;
+use rocket::{get, serde::json::Json, State};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    access::Viewer,
+    reload::LiveConfig,
+    session::{generate_id, SessionStore},
+};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CaseLinkingConfig {
+    // Sessions disclosing the same value for this attribute are linked
+    attribute: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Case {
+    id: String,
+    created_at: SystemTime,
+    sessions: Vec<String>,
+}
+
+impl Case {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn sessions(&self) -> &[String] {
+        &self.sessions
+    }
+}
+
+// Links according to the current configuration, so case linking can be
+// switched on or off by reloading, keeping the cases linked so far
+#[derive(Clone)]
+pub struct CaseStore {
+    live: LiveConfig,
+    // Keyed by a hash of the identifying attribute, to avoid keeping it around
+    cases: Arc<Mutex<HashMap<String, Case>>>,
+}
+
+impl CaseStore {
+    pub fn new(live: LiveConfig) -> CaseStore {
+        CaseStore {
+            live,
+            cases: Arc::default(),
+        }
+    }
+
+    // Returns the id of the case the session ended up in, and whether that
+    // case was newly created
+    pub fn link(&self, session_id: &str, auth_result: &AuthResult) -> Option<(String, bool)> {
+        let config = self.live.current();
+        let value = auth_result
+            .attributes
+            .as_ref()?
+            .get(&config.case_linking()?.attribute)?;
+        let subject = hex::encode(Sha256::digest(value.as_bytes()));
+
+        let mut cases = self.cases.lock().unwrap();
+        let created = !cases.contains_key(&subject);
+        let case = cases.entry(subject).or_insert_with(|| Case {
+            id: generate_id(),
+            created_at: SystemTime::now(),
+            sessions: Vec::new(),
+        });
+        if !case.sessions.iter().any(|id| id == session_id) {
+            case.sessions.push(session_id.to_string());
+        }
+        Some((case.id.clone(), created))
+    }
+
+    pub fn list(&self) -> Vec<Case> {
+        let mut cases: Vec<Case> = self.cases.lock().unwrap().values().cloned().collect();
+        cases.sort_by_key(|case| case.created_at);
+        cases
+    }
+
+    pub fn get(&self, id: &str) -> Option<Case> {
+        self.cases
+            .lock()
+            .unwrap()
+            .values()
+            .find(|case| case.id == id)
+            .cloned()
+    }
+}
+
+#[get("/internal/cases")]
+pub fn list(_viewer: Viewer, sessions: &State<SessionStore>) -> Json<Vec<Case>> {
+    Json(sessions.cases().map(CaseStore::list).unwrap_or_default())
+}
+
+#[get("/internal/cases/<id>")]
+pub fn get(_viewer: Viewer, id: String, sessions: &State<SessionStore>) -> Option<Json<Case>> {
+    sessions.cases()?.get(&id).map(Json)
+}
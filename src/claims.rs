@@ -0,0 +1,95 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{clock::Clock, Error};
+
+fn default_leeway() -> u64 {
+    60
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ClaimValidationConfig {
+    // Seconds of clock difference with the auth provider to tolerate
+    #[serde(default = "default_leeway")]
+    leeway: u64,
+    #[serde(default = "default_true")]
+    exp: bool,
+    #[serde(default = "default_true")]
+    nbf: bool,
+    #[serde(default = "default_true")]
+    iat: bool,
+    // Seconds after iat an auth result is still accepted
+    max_age: Option<u64>,
+}
+
+// NumericDate claims may carry fractional seconds
+fn numeric_date(claims: &Value, name: &str) -> Option<u64> {
+    claims[name].as_f64().map(|date| date as u64)
+}
+
+impl ClaimValidationConfig {
+    // Checks the standard time claims present in the auth result token
+    // against the plugin's clock
+    pub fn validate(&self, claims: &Value, clock: &Clock) -> Result<(), Error> {
+        let now = clock.unix_now();
+
+        if self.exp {
+            if let Some(exp) = numeric_date(claims, "exp") {
+                if exp.saturating_add(self.leeway) < now {
+                    return Err(Error::Expired);
+                }
+            }
+        }
+        if self.nbf {
+            if let Some(nbf) = numeric_date(claims, "nbf") {
+                if nbf > now.saturating_add(self.leeway) {
+                    return Err(Error::NotYetValid);
+                }
+            }
+        }
+        if self.iat {
+            if let Some(iat) = numeric_date(claims, "iat") {
+                if iat > now.saturating_add(self.leeway) {
+                    return Err(Error::NotYetValid);
+                }
+                if let Some(max_age) = self.max_age {
+                    if iat.saturating_add(max_age).saturating_add(self.leeway) < now {
+                        return Err(Error::Expired);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Checks the auth result token was issued by and meant for the expected
+// parties, so tokens destined for another plugin are refused
+pub fn check_recipient(
+    claims: &Value,
+    issuer: Option<&str>,
+    audience: Option<&str>,
+) -> Result<(), Error> {
+    if let Some(issuer) = issuer {
+        let iss = claims["iss"].as_str();
+        if iss != Some(issuer) {
+            return Err(Error::UnexpectedIssuer(iss.map(|iss| iss.to_string())));
+        }
+    }
+    if let Some(audience) = audience {
+        // aud is either a single string or an array of them
+        let matches = match &claims["aud"] {
+            Value::String(aud) => aud == audience,
+            Value::Array(auds) => auds.iter().any(|aud| aud.as_str() == Some(audience)),
+            _ => false,
+        };
+        if !matches {
+            return Err(Error::UnexpectedAudience);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use id_contact_jwt::sign_and_encrypt_auth_result;
+use id_contact_proto::{AuthResult, AuthStatus};
+use rocket::{post, serde::json::Json};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::info;
+
+use crate::{
+    access::Operator,
+    config::{ClientConfig, Config},
+    logging::RedactedAttributes,
+    Error,
+};
+
+#[derive(Deserialize, Debug)]
+pub struct SimulateStartRequest {
+    purpose: String,
+    // Base url of the plugin to start communication with, the configured
+    // one if absent
+    url: Option<String>,
+    // Attributes of the auth result to attach, none is attached if absent
+    attributes: Option<HashMap<String, String>>,
+    // Attach a failed auth result instead
+    #[serde(default)]
+    failed: bool,
+}
+
+// Signs and encrypts an auth result for the plugin on the other end
+fn auth_result(
+    config: &ClientConfig,
+    request: &SimulateStartRequest,
+) -> Result<Option<String>, Error> {
+    let auth_result = if request.failed {
+        AuthResult {
+            status: AuthStatus::Failed,
+            attributes: None,
+            session_url: None,
+        }
+    } else {
+        match &request.attributes {
+            Some(attributes) => AuthResult {
+                status: AuthStatus::Succes,
+                attributes: Some(attributes.clone()),
+                session_url: None,
+            },
+            None => return Ok(None),
+        }
+    };
+    Ok(Some(sign_and_encrypt_auth_result(
+        &auth_result,
+        config.signer(),
+        config.encrypter(),
+    )?))
+}
+
+// Sends a start_communication request to another plugin, as a core would,
+// returning its response as is
+pub async fn start_communication(
+    url: &str,
+    purpose: &str,
+    auth_result: Option<String>,
+) -> Result<Value, Error> {
+    let response = reqwest::Client::new()
+        .post(format!("{}/start_communication", url))
+        .json(&json!({ "purpose": purpose, "auth_result": auth_result }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(response)
+}
+
+#[post("/internal/simulate_start", data = "<request>")]
+pub async fn simulate_start(
+    _operator: Operator,
+    request: Json<SimulateStartRequest>,
+    config: &Config,
+) -> Result<Json<Value>, Error> {
+    let client = config.client().ok_or(Error::ClientNotConfigured)?;
+    let url = request
+        .url
+        .as_deref()
+        .or_else(|| client.url())
+        .ok_or(Error::ClientNotConfigured)?;
+    if let Some(attributes) = &request.attributes {
+        info!(
+            "Simulating start of communication for {} at {} with {}",
+            request.purpose,
+            url,
+            RedactedAttributes(attributes)
+        );
+    } else {
+        info!(
+            "Simulating start of communication for {} at {}",
+            request.purpose, url
+        );
+    }
+
+    let auth_result = auth_result(client, &request)?;
+    let response = start_communication(url, &request.purpose, auth_result).await?;
+    info!("Plugin at {} responded with {}", url, response);
+    Ok(Json(response))
+}
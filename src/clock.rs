@@ -0,0 +1,81 @@
+use std::{
+    convert::Infallible,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use rocket::{
+    request::{FromRequest, Outcome},
+    Request,
+};
+
+use crate::access::{self, Role};
+
+// Signed number of seconds the plugin should consider itself ahead of the
+// actual time for the session started by the request, honored for callers
+// with at least the operator role
+pub const CLOCK_SKEW_HEADER: &str = "X-Simulated-Clock-Skew";
+
+// Largest skew accepted in either direction, ten years in seconds
+pub const MAX_SKEW: u64 = 10 * 365 * 24 * 60 * 60;
+
+// Source of the current time, possibly skewed to simulate a plugin whose
+// clock is off, so skew tolerance of the rest of the stack can be tested
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Clock {
+    skew: i64,
+}
+
+impl Clock {
+    pub fn with_skew(skew: i64) -> Clock {
+        Clock { skew }
+    }
+
+    pub fn skew(&self) -> i64 {
+        self.skew
+    }
+
+    pub fn now(&self) -> SystemTime {
+        let now = SystemTime::now();
+        let skew = Duration::from_secs(self.skew.unsigned_abs().min(MAX_SKEW));
+        let skewed = if self.skew >= 0 {
+            now.checked_add(skew)
+        } else {
+            now.checked_sub(skew)
+        };
+        skewed.unwrap_or(now)
+    }
+
+    pub fn unix_now(&self) -> u64 {
+        self.now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Clock {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let config = crate::reload::current(request);
+        let skew = request
+            .headers()
+            .get_one(CLOCK_SKEW_HEADER)
+            .filter(|_| {
+                config.map_or(false, |config| {
+                    access::check(
+                        config,
+                        request.headers().get_one("Authorization"),
+                        Role::Operator,
+                    )
+                    .is_ok()
+                })
+            })
+            .and_then(|skew| skew.parse::<i64>().ok())
+            .filter(|skew| skew.unsigned_abs() <= MAX_SKEW)
+            .or_else(|| Some(config?.clock_skew()))
+            .unwrap_or_default();
+        Outcome::Success(Clock::with_skew(skew))
+    }
+}
@@ -0,0 +1,87 @@
+use reqwest::RequestBuilder;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::config::Config;
+
+// Prefix of the CloudEvents type, followed by the event kind
+const TYPE_PREFIX: &str = "nl.id-contact.comm-test.";
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookFormat {
+    // The event as a JSON body, as before
+    Plain,
+    // CloudEvents 1.0, attributes and data together in the JSON body
+    CloudEventsStructured,
+    // CloudEvents 1.0, attributes in ce- headers and the event as body
+    CloudEventsBinary,
+}
+
+impl Default for WebhookFormat {
+    fn default() -> WebhookFormat {
+        WebhookFormat::Plain
+    }
+}
+
+// Wraps lifecycle webhook requests in the configured envelope
+#[derive(Clone, Debug)]
+pub struct Envelope {
+    format: WebhookFormat,
+    source: String,
+}
+
+impl Envelope {
+    pub fn new(config: &Config) -> Envelope {
+        Envelope {
+            format: config.webhook_format(),
+            source: config.server_url().to_string(),
+        }
+    }
+
+    pub fn wrap(
+        &self,
+        request: RequestBuilder,
+        id: &str,
+        kind: &str,
+        subject: Option<&str>,
+        data: &Value,
+    ) -> RequestBuilder {
+        let event_type = format!("{}{}", TYPE_PREFIX, kind);
+        match self.format {
+            WebhookFormat::Plain => request
+                .header("Content-Type", "application/json")
+                .body(data.to_string()),
+            WebhookFormat::CloudEventsStructured => {
+                let mut event = json!({
+                    "specversion": "1.0",
+                    "id": id,
+                    "source": self.source,
+                    "type": event_type,
+                    "datacontenttype": "application/json",
+                    "data": data,
+                });
+                if let Some(subject) = subject {
+                    event["subject"] = json!(subject);
+                }
+                request
+                    .header("Content-Type", "application/cloudevents+json")
+                    .body(event.to_string())
+            }
+            WebhookFormat::CloudEventsBinary => {
+                let request = request
+                    .header("ce-specversion", "1.0")
+                    .header("ce-id", id)
+                    .header("ce-source", &self.source)
+                    .header("ce-type", event_type);
+                let request = match subject {
+                    Some(subject) => request.header("ce-subject", subject),
+                    None => request,
+                };
+                request
+                    .header("Content-Type", "application/json")
+                    .body(data.to_string())
+            }
+        }
+    }
+}
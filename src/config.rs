@@ -1,14 +1,61 @@
-use id_contact_jwt::{EncryptionKeyConfig, SignKeyConfig};
-use serde::Deserialize;
-use std::{convert::TryFrom, error::Error as StdError, fmt::Display};
+use serde::{Deserialize, Serialize};
+#[cfg(any(feature = "grpc", feature = "ws"))]
+use std::net::SocketAddr;
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    error::Error as StdError,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
 
-use josekit::{jwe::JweDecrypter, jws::JwsVerifier};
+use josekit::{
+    jwe::{JweDecrypter, JweEncrypter},
+    jws::{JwsSigner, JwsVerifier},
+};
+use rocket::figment::{
+    providers::{Env, Serialized},
+    Figment,
+};
+
+#[cfg(feature = "kafka")]
+use crate::kafka::KafkaConfig;
+#[cfg(feature = "outbox")]
+use crate::outbox::OutboxConfig;
+use crate::{
+    access::AdminToken,
+    assurance::AssuranceConfig,
+    cases::CaseLinkingConfig,
+    claims::ClaimValidationConfig,
+    cloudevents::WebhookFormat,
+    core_auth::ClientIdentityConfig,
+    duplicates::DuplicateDetectionConfig,
+    expiry::SessionExpiryConfig,
+    faults::Fault,
+    formatting::AttributeFormat,
+    headers::HeaderRule,
+    jti::ReplayProtectionConfig,
+    jwks::JwksVerifier,
+    key_usage::{KeyKind, KeyRotationConfig},
+    keys::{self, EncryptionKeyConfig, PublicJwk, SignKeyConfig},
+    mirror::MirrorConfig,
+    oidc::OidcConfig,
+    personas::Persona,
+    purposes::PurposeConfig,
+    request_log::RequestLogConfig,
+    responses::ResponseTemplate,
+    retry::RetryConfig,
+    url_signing::UrlSigningConfig,
+};
 
 #[derive(Debug)]
 pub enum Error {
     Yaml(serde_yaml::Error),
     Json(serde_json::Error),
-    Jwt(id_contact_jwt::Error),
+    Jose(josekit::JoseError),
+    NoDecryptionKeys,
+    InvalidUrl(&'static str, String),
+    InvalidClockSkew(i64),
 }
 
 impl From<serde_yaml::Error> for Error {
@@ -23,9 +70,9 @@ impl From<serde_json::Error> for Error {
     }
 }
 
-impl From<id_contact_jwt::Error> for Error {
-    fn from(e: id_contact_jwt::Error) -> Error {
-        Error::Jwt(e)
+impl From<josekit::JoseError> for Error {
+    fn from(e: josekit::JoseError) -> Error {
+        Error::Jose(e)
     }
 }
 
@@ -34,7 +81,15 @@ impl Display for Error {
         match self {
             Error::Yaml(e) => e.fmt(f),
             Error::Json(e) => e.fmt(f),
-            Error::Jwt(e) => e.fmt(f),
+            Error::Jose(e) => e.fmt(f),
+            Error::NoDecryptionKeys => f.write_str("At least one decryption key is required"),
+            Error::InvalidUrl(field, reason) => write!(f, "Invalid {}: {}", field, reason),
+            Error::InvalidClockSkew(skew) => write!(
+                f,
+                "Invalid clock_skew {}: at most {} seconds supported",
+                skew,
+                crate::clock::MAX_SKEW
+            ),
         }
     }
 }
@@ -44,50 +99,586 @@ impl StdError for Error {
         match self {
             Error::Yaml(e) => Some(e),
             Error::Json(e) => Some(e),
-            Error::Jwt(e) => Some(e),
+            Error::Jose(e) => Some(e),
+            Error::NoDecryptionKeys => None,
+            Error::InvalidUrl(_, _) => None,
+            Error::InvalidClockSkew(_) => None,
         }
     }
 }
 
+#[cfg(feature = "amqp")]
+#[derive(Deserialize, Debug)]
+pub struct AmqpConfig {
+    pub uri: String,
+    pub request_queue: String,
+    pub reply_queue: String,
+}
+
+#[cfg(feature = "nats")]
+#[derive(Deserialize, Debug)]
+pub struct NatsConfig {
+    pub url: String,
+    pub subject: String,
+}
+
+// Tuning of inbound connections, for high rates of attr_url deliveries
+#[derive(Deserialize, Debug, Default)]
+pub struct ConnectionConfig {
+    // Seconds, passed on to Rocket's own keep_alive setting
+    pub keep_alive: Option<u32>,
+    // Not supported by Rocket 0.5.0-rc.1, which only negotiates HTTP/2 over TLS
+    #[serde(default)]
+    pub http2_prior_knowledge: bool,
+    // Not supported by Rocket 0.5.0-rc.1 either
+    pub max_concurrent_streams: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RedirectConfig {
+    pub default_url: Option<String>,
+    #[serde(default)]
+    pub allowed_urls: Vec<String>,
+    pub secret: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct RawProviderConfig {
+    signing_privkey: SignKeyConfig,
+    encryption_pubkey: EncryptionKeyConfig,
+}
+
+#[derive(Debug)]
+pub struct ProviderConfig {
+    signer: Box<dyn JwsSigner>,
+    encrypter: Box<dyn JweEncrypter>,
+}
+
+impl TryFrom<RawProviderConfig> for ProviderConfig {
+    type Error = Error;
+    fn try_from(config: RawProviderConfig) -> Result<ProviderConfig, Error> {
+        Ok(ProviderConfig {
+            signer: Box::<dyn JwsSigner>::try_from(config.signing_privkey)?,
+            encrypter: Box::<dyn JweEncrypter>::try_from(config.encryption_pubkey)?,
+        })
+    }
+}
+
+impl ProviderConfig {
+    pub fn signer(&self) -> &dyn JwsSigner {
+        self.signer.as_ref()
+    }
+
+    pub fn encrypter(&self) -> &dyn JweEncrypter {
+        self.encrypter.as_ref()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct RawClientConfig {
+    url: Option<String>,
+    signing_privkey: SignKeyConfig,
+    encryption_pubkey: EncryptionKeyConfig,
+}
+
+#[derive(Debug)]
+pub struct ClientConfig {
+    url: Option<String>,
+    signer: Box<dyn JwsSigner>,
+    encrypter: Box<dyn JweEncrypter>,
+}
+
+impl TryFrom<RawClientConfig> for ClientConfig {
+    type Error = Error;
+    fn try_from(config: RawClientConfig) -> Result<ClientConfig, Error> {
+        if let Some(url) = &config.url {
+            validate_url("client.url", url)?;
+        }
+        Ok(ClientConfig {
+            url: config.url,
+            signer: Box::<dyn JwsSigner>::try_from(config.signing_privkey)?,
+            encrypter: Box::<dyn JweEncrypter>::try_from(config.encryption_pubkey)?,
+        })
+    }
+}
+
+impl ClientConfig {
+    // Plugin to send requests to when the trigger names none
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    pub fn signer(&self) -> &dyn JwsSigner {
+        self.signer.as_ref()
+    }
+
+    pub fn encrypter(&self) -> &dyn JweEncrypter {
+        self.encrypter.as_ref()
+    }
+}
+
+// What to do with an inline auth_result that fails to decrypt or verify
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum InvalidAuthResultPolicy {
+    // Fail the start request
+    Reject,
+    // Start the session as if no auth_result was sent
+    Ignore,
+    // Start the session, flagging the invalid auth_result on it
+    Record,
+}
+
+impl Default for InvalidAuthResultPolicy {
+    fn default() -> InvalidAuthResultPolicy {
+        InvalidAuthResultPolicy::Reject
+    }
+}
+
+// What to do when a session that already has an auth result receives a
+// different one
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateDeliveryPolicy {
+    // Keep the auth result received first
+    FirstWins,
+    // Replace it with the one received last
+    LastWins,
+    // Keep the first, failing the later delivery
+    Reject,
+}
+
+impl Default for DuplicateDeliveryPolicy {
+    fn default() -> DuplicateDeliveryPolicy {
+        DuplicateDeliveryPolicy::LastWins
+    }
+}
+
+fn default_signature_header() -> String {
+    "X-Signature".to_string()
+}
+
+#[derive(Deserialize, Debug)]
+struct RawWebhookSinkConfig {
+    #[serde(default = "default_signature_header")]
+    signature_header: String,
+    hmac_secret: Option<String>,
+    jws_pubkey: Option<SignKeyConfig>,
+}
+
+#[derive(Debug)]
+pub struct WebhookSinkConfig {
+    signature_header: String,
+    hmac_secret: Option<String>,
+    jws_validator: Option<Box<dyn JwsVerifier>>,
+}
+
+impl TryFrom<RawWebhookSinkConfig> for WebhookSinkConfig {
+    type Error = Error;
+    fn try_from(config: RawWebhookSinkConfig) -> Result<WebhookSinkConfig, Error> {
+        Ok(WebhookSinkConfig {
+            signature_header: config.signature_header,
+            hmac_secret: config.hmac_secret,
+            jws_validator: config
+                .jws_pubkey
+                .map(Box::<dyn JwsVerifier>::try_from)
+                .transpose()?,
+        })
+    }
+}
+
+impl WebhookSinkConfig {
+    pub fn signature_header(&self) -> &str {
+        &self.signature_header
+    }
+
+    pub fn hmac_secret(&self) -> Option<&str> {
+        self.hmac_secret.as_deref()
+    }
+
+    pub fn jws_validator(&self) -> Option<&dyn JwsVerifier> {
+        self.jws_validator.as_deref()
+    }
+}
+
+fn default_method_tag() -> String {
+    "comm-test".to_string()
+}
+
+fn default_method_name() -> String {
+    "Test communication plugin".to_string()
+}
+
+fn default_recording_capacity() -> usize {
+    1000
+}
+
+fn default_crash_report_dir() -> PathBuf {
+    PathBuf::from("crash-reports")
+}
+
+// Seconds clients may cache the key set and metadata
+fn default_cache_max_age() -> u64 {
+    300
+}
+
 fn default_as_true() -> bool {
     true
 }
 
+// Rocket's own configuration sources, overridden by COMMTEST_ environment
+// variables. Nested fields are separated by a double underscore, as in
+// COMMTEST_DECRYPTION_PRIVKEY__KEY, or a whole table can be given inline.
+pub fn figment() -> Figment {
+    let figment = rocket::Config::figment().merge(Env::prefixed("COMMTEST_").split("__").global());
+    // Rocket reads keep_alive from its own key
+    match figment.extract_inner::<u32>("connection.keep_alive") {
+        Ok(keep_alive) => figment.merge(Serialized::global("keep_alive", keep_alive)),
+        Err(_) => figment,
+    }
+}
+
+// A list allows rolling over to a new key while results encrypted for the
+// old one are still in flight
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum DecryptionKeys {
+    One(EncryptionKeyConfig),
+    Many(Vec<EncryptionKeyConfig>),
+}
+
 #[derive(Deserialize, Debug)]
 struct RawConfig {
+    #[serde(default)]
+    allow_unverified_saml: bool,
     server_url: String,
     internal_url: String,
     #[serde(default = "default_as_true")]
     use_attr_url: bool,
-    decryption_privkey: EncryptionKeyConfig,
+    decryption_privkey: DecryptionKeys,
     signature_pubkey: SignKeyConfig,
+    request_log: Option<RequestLogConfig>,
+    #[serde(default)]
+    self_test: bool,
+    #[serde(default = "default_method_tag")]
+    method_tag: String,
+    #[serde(default = "default_method_name")]
+    method_name: String,
+    method_image_path: Option<String>,
+    redirect: Option<RedirectConfig>,
+    duplicate_detection: Option<DuplicateDetectionConfig>,
+    mirror: Option<MirrorConfig>,
+    attribute_provider: Option<RawProviderConfig>,
+    #[serde(default)]
+    personas: HashMap<String, Persona>,
+    webhook_sink: Option<RawWebhookSinkConfig>,
+    #[serde(default)]
+    response_headers: Vec<HeaderRule>,
+    core_pubkey: Option<SignKeyConfig>,
+    client_url_signing: Option<UrlSigningConfig>,
+    #[serde(default)]
+    responses: HashMap<String, ResponseTemplate>,
+    #[serde(default)]
+    faults: Vec<Fault>,
+    #[serde(default = "default_recording_capacity")]
+    recording_capacity: usize,
+    #[serde(default = "default_crash_report_dir")]
+    crash_report_dir: PathBuf,
+    #[serde(default)]
+    admin_tokens: Vec<AdminToken>,
+    #[serde(default)]
+    authenticators: HashMap<String, SignKeyConfig>,
+    case_linking: Option<CaseLinkingConfig>,
+    #[serde(default)]
+    on_invalid_auth_result: InvalidAuthResultPolicy,
+    admin_signing_privkey: Option<SignKeyConfig>,
+    // Seconds, applied to sessions not started with an explicit skew
+    #[serde(default)]
+    clock_skew: i64,
+    #[serde(default)]
+    config_reload: bool,
+    #[serde(default)]
+    ui_post_bridge: bool,
+    assurance: Option<AssuranceConfig>,
+    #[serde(default)]
+    check_internal_url: bool,
+    session_expiry: Option<SessionExpiryConfig>,
+    #[serde(default)]
+    purposes: HashMap<String, PurposeConfig>,
+    claim_validation: Option<ClaimValidationConfig>,
+    key_rotation: Option<KeyRotationConfig>,
+    expected_issuer: Option<String>,
+    expected_audience: Option<String>,
+    replay_protection: Option<ReplayProtectionConfig>,
+    client: Option<RawClientConfig>,
+    #[serde(default)]
+    connection: ConnectionConfig,
+    #[serde(default)]
+    on_duplicate_delivery: DuplicateDeliveryPolicy,
+    #[serde(default = "default_cache_max_age")]
+    cache_max_age: u64,
+    client_url_handles: Option<u64>,
+    #[serde(default)]
+    attribute_formats: HashMap<String, AttributeFormat>,
+    admin_oidc: Option<OidcConfig>,
+    #[serde(default)]
+    webhook_format: WebhookFormat,
+    retry_queue: Option<RetryConfig>,
+    core_client_identity: Option<ClientIdentityConfig>,
+    #[cfg(feature = "grpc")]
+    grpc_address: Option<SocketAddr>,
+    #[cfg(feature = "amqp")]
+    amqp: Option<AmqpConfig>,
+    #[cfg(feature = "nats")]
+    nats: Option<NatsConfig>,
+    #[cfg(feature = "outbox")]
+    outbox: Option<OutboxConfig>,
+    #[cfg(feature = "kafka")]
+    kafka: Option<KafkaConfig>,
+    #[cfg(feature = "ws")]
+    events_address: Option<SocketAddr>,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(try_from = "RawConfig")]
 pub struct Config {
+    allow_unverified_saml: bool,
     server_url: String,
     internal_url: String,
     use_attr_url: bool,
-    decrypter: Box<dyn JweDecrypter>,
+    decrypters: Vec<(String, Box<dyn JweDecrypter>)>,
     validator: Box<dyn JwsVerifier>,
+    // Kept separately to be able to tell whether any keys were fetched
+    remote_validator: Option<JwksVerifier>,
+    public_keys: Vec<PublicJwk>,
+    request_log: Option<RequestLogConfig>,
+    self_test: bool,
+    method_tag: String,
+    method_name: String,
+    method_image_path: Option<String>,
+    redirect: Option<RedirectConfig>,
+    duplicate_detection: Option<DuplicateDetectionConfig>,
+    mirror: Option<MirrorConfig>,
+    attribute_provider: Option<ProviderConfig>,
+    personas: HashMap<String, Persona>,
+    webhook_sink: Option<WebhookSinkConfig>,
+    response_headers: Vec<HeaderRule>,
+    core_validator: Option<Box<dyn JwsVerifier>>,
+    client_url_signing: Option<UrlSigningConfig>,
+    responses: HashMap<String, ResponseTemplate>,
+    faults: Vec<Fault>,
+    recording_capacity: usize,
+    crash_report_dir: PathBuf,
+    admin_tokens: Vec<AdminToken>,
+    authenticators: HashMap<String, Box<dyn JwsVerifier>>,
+    case_linking: Option<CaseLinkingConfig>,
+    on_invalid_auth_result: InvalidAuthResultPolicy,
+    admin_signer: Option<(String, Box<dyn JwsSigner>)>,
+    clock_skew: i64,
+    config_reload: bool,
+    ui_post_bridge: bool,
+    assurance: Option<AssuranceConfig>,
+    check_internal_url: bool,
+    session_expiry: Option<SessionExpiryConfig>,
+    purposes: HashMap<String, PurposeConfig>,
+    claim_validation: Option<ClaimValidationConfig>,
+    key_rotation: Option<KeyRotationConfig>,
+    expected_issuer: Option<String>,
+    expected_audience: Option<String>,
+    replay_protection: Option<ReplayProtectionConfig>,
+    client: Option<ClientConfig>,
+    connection: ConnectionConfig,
+    on_duplicate_delivery: DuplicateDeliveryPolicy,
+    cache_max_age: u64,
+    client_url_handles: Option<u64>,
+    attribute_formats: HashMap<String, AttributeFormat>,
+    admin_oidc: Option<OidcConfig>,
+    webhook_format: WebhookFormat,
+    retry_queue: Option<RetryConfig>,
+    core_client_identity: Option<ClientIdentityConfig>,
+    #[cfg(feature = "grpc")]
+    grpc_address: Option<SocketAddr>,
+    #[cfg(feature = "amqp")]
+    amqp: Option<AmqpConfig>,
+    #[cfg(feature = "nats")]
+    nats: Option<NatsConfig>,
+    #[cfg(feature = "outbox")]
+    outbox: Option<OutboxConfig>,
+    #[cfg(feature = "kafka")]
+    kafka: Option<KafkaConfig>,
+    #[cfg(feature = "ws")]
+    events_address: Option<SocketAddr>,
+}
+
+// Urls are used as a base to append paths to, so they need to be absolute
+// http(s) urls without a trailing slash
+fn validate_url(field: &'static str, url: &str) -> Result<(), Error> {
+    let invalid = |reason: String| Error::InvalidUrl(field, reason);
+    if !url.contains("://") {
+        return Err(invalid(format!(
+            "{} is missing a scheme, such as https://",
+            url
+        )));
+    }
+    let parsed = reqwest::Url::parse(url)
+        .map_err(|e| invalid(format!("{} is not a valid url ({})", url, e)))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(invalid(format!("{} is not an http(s) url", url)));
+    }
+    if url.ends_with('/') {
+        return Err(invalid(format!("{} should not end with a slash", url)));
+    }
+    if parsed.query().is_some() || parsed.fragment().is_some() {
+        return Err(invalid(format!(
+            "{} should not have a query or fragment",
+            url
+        )));
+    }
+    Ok(())
 }
 
 // This tryfrom can be removed once try_from for fields lands in serde
 impl TryFrom<RawConfig> for Config {
     type Error = Error;
     fn try_from(config: RawConfig) -> Result<Config, Error> {
+        validate_url("server_url", &config.server_url)?;
+        validate_url("internal_url", &config.internal_url)?;
+        if config.clock_skew.unsigned_abs() > crate::clock::MAX_SKEW {
+            return Err(Error::InvalidClockSkew(config.clock_skew));
+        }
+
+        let decryption_keys = match config.decryption_privkey {
+            DecryptionKeys::One(key) => vec![key],
+            DecryptionKeys::Many(keys) => keys,
+        };
+        if decryption_keys.is_empty() {
+            return Err(Error::NoDecryptionKeys);
+        }
+
+        let mut public_keys = Vec::new();
+        let mut decrypters = Vec::new();
+        for key in decryption_keys {
+            let public_key = keys::public_encryption_jwk(&key)?;
+            let kid = public_key["kid"].as_str().unwrap_or_default().to_string();
+            decrypters.push((kid, Box::<dyn JweDecrypter>::try_from(key)?));
+            public_keys.push(public_key);
+        }
+        if let Some(provider) = &config.attribute_provider {
+            public_keys.push(keys::public_signing_jwk(&provider.signing_privkey)?);
+        }
+        let admin_signer = match config.admin_signing_privkey {
+            Some(key) => {
+                let public_key = keys::public_signing_jwk(&key)?;
+                let kid = public_key["kid"].as_str().unwrap_or_default().to_string();
+                public_keys.push(public_key);
+                Some((kid, Box::<dyn JwsSigner>::try_from(key)?))
+            }
+            None => None,
+        };
+
+        let (validator, remote_validator) = match config.signature_pubkey {
+            SignKeyConfig::Jwks {
+                url,
+                algorithm,
+                refresh_interval,
+            } => {
+                let validator = JwksVerifier::new(url, algorithm, refresh_interval);
+                (
+                    Box::new(validator.clone()) as Box<dyn JwsVerifier>,
+                    Some(validator),
+                )
+            }
+            key => (Box::<dyn JwsVerifier>::try_from(key)?, None),
+        };
+
         Ok(Config {
+            allow_unverified_saml: config.allow_unverified_saml,
             server_url: config.server_url,
             internal_url: config.internal_url,
             use_attr_url: config.use_attr_url,
-            decrypter: Box::<dyn JweDecrypter>::try_from(config.decryption_privkey)?,
-            validator: Box::<dyn JwsVerifier>::try_from(config.signature_pubkey)?,
+            decrypters,
+            validator,
+            remote_validator,
+            public_keys,
+            request_log: config.request_log,
+            self_test: config.self_test,
+            method_tag: config.method_tag,
+            method_name: config.method_name,
+            method_image_path: config.method_image_path,
+            redirect: config.redirect,
+            duplicate_detection: config.duplicate_detection,
+            mirror: config.mirror,
+            attribute_provider: config
+                .attribute_provider
+                .map(ProviderConfig::try_from)
+                .transpose()?,
+            personas: config.personas,
+            webhook_sink: config
+                .webhook_sink
+                .map(WebhookSinkConfig::try_from)
+                .transpose()?,
+            response_headers: config.response_headers,
+            core_validator: config
+                .core_pubkey
+                .map(Box::<dyn JwsVerifier>::try_from)
+                .transpose()?,
+            client_url_signing: config.client_url_signing,
+            responses: config.responses,
+            faults: config.faults,
+            recording_capacity: config.recording_capacity,
+            crash_report_dir: config.crash_report_dir,
+            admin_tokens: config.admin_tokens,
+            authenticators: config
+                .authenticators
+                .into_iter()
+                .map(|(issuer, key)| Ok((issuer, Box::<dyn JwsVerifier>::try_from(key)?)))
+                .collect::<Result<_, Error>>()?,
+            case_linking: config.case_linking,
+            on_invalid_auth_result: config.on_invalid_auth_result,
+            admin_signer,
+            clock_skew: config.clock_skew,
+            config_reload: config.config_reload,
+            ui_post_bridge: config.ui_post_bridge,
+            assurance: config.assurance,
+            check_internal_url: config.check_internal_url,
+            session_expiry: config.session_expiry,
+            purposes: config.purposes,
+            claim_validation: config.claim_validation,
+            key_rotation: config.key_rotation,
+            expected_issuer: config.expected_issuer,
+            expected_audience: config.expected_audience,
+            replay_protection: config.replay_protection,
+            client: config.client.map(ClientConfig::try_from).transpose()?,
+            connection: config.connection,
+            on_duplicate_delivery: config.on_duplicate_delivery,
+            cache_max_age: config.cache_max_age,
+            client_url_handles: config.client_url_handles,
+            attribute_formats: config.attribute_formats,
+            admin_oidc: config.admin_oidc,
+            webhook_format: config.webhook_format,
+            retry_queue: config.retry_queue,
+            core_client_identity: config.core_client_identity,
+            #[cfg(feature = "grpc")]
+            grpc_address: config.grpc_address,
+            #[cfg(feature = "amqp")]
+            amqp: config.amqp,
+            #[cfg(feature = "nats")]
+            nats: config.nats,
+            #[cfg(feature = "outbox")]
+            outbox: config.outbox,
+            #[cfg(feature = "kafka")]
+            kafka: config.kafka,
+            #[cfg(feature = "ws")]
+            events_address: config.events_address,
         })
     }
 }
 
 impl Config {
+    pub fn allow_unverified_saml(&self) -> bool {
+        self.allow_unverified_saml
+    }
+
     pub fn server_url(&self) -> &str {
         &self.server_url
     }
@@ -96,15 +687,313 @@ impl Config {
         &self.internal_url
     }
 
+    // The first configured key, used for everything but decryption
     pub fn decrypter(&self) -> &dyn JweDecrypter {
-        self.decrypter.as_ref()
+        self.decrypters[0].1.as_ref()
+    }
+
+    // Only the key matching kid if there is one, otherwise all keys in order
+    pub fn decrypters(&self, kid: Option<&str>) -> Vec<&dyn JweDecrypter> {
+        self.keyed_decrypters(kid)
+            .into_iter()
+            .map(|(_, decrypter)| decrypter)
+            .collect()
+    }
+
+    // As decrypters, along with the kid of each key
+    pub fn keyed_decrypters(&self, kid: Option<&str>) -> Vec<(&str, &dyn JweDecrypter)> {
+        match self
+            .decrypters
+            .iter()
+            .find(|(id, _)| Some(id.as_str()) == kid)
+        {
+            Some((id, decrypter)) => vec![(id.as_str(), decrypter.as_ref())],
+            None => self
+                .decrypters
+                .iter()
+                .map(|(id, decrypter)| (id.as_str(), decrypter.as_ref()))
+                .collect(),
+        }
     }
 
     pub fn validator(&self) -> &dyn JwsVerifier {
         self.validator.as_ref()
     }
 
+    pub fn remote_validator(&self) -> Option<&JwksVerifier> {
+        self.remote_validator.as_ref()
+    }
+
+    pub fn public_keys(&self) -> &[PublicJwk] {
+        &self.public_keys
+    }
+
     pub fn use_attr_url(&self) -> bool {
         self.use_attr_url
     }
+
+    pub fn request_log(&self) -> Option<&RequestLogConfig> {
+        self.request_log.as_ref()
+    }
+
+    pub fn self_test(&self) -> bool {
+        self.self_test
+    }
+
+    pub fn method_tag(&self) -> &str {
+        &self.method_tag
+    }
+
+    pub fn method_name(&self) -> &str {
+        &self.method_name
+    }
+
+    pub fn method_image_path(&self) -> Option<&str> {
+        self.method_image_path.as_deref()
+    }
+
+    pub fn redirect(&self) -> Option<&RedirectConfig> {
+        self.redirect.as_ref()
+    }
+
+    pub fn duplicate_detection(&self) -> Option<&DuplicateDetectionConfig> {
+        self.duplicate_detection.as_ref()
+    }
+
+    pub fn mirror(&self) -> Option<&MirrorConfig> {
+        self.mirror.as_ref()
+    }
+
+    pub fn attribute_provider(&self) -> Option<&ProviderConfig> {
+        self.attribute_provider.as_ref()
+    }
+
+    pub fn persona(&self, name: &str) -> Option<&Persona> {
+        self.personas.get(name)
+    }
+
+    pub fn webhook_sink(&self) -> Option<&WebhookSinkConfig> {
+        self.webhook_sink.as_ref()
+    }
+
+    pub fn response_headers(&self) -> &[HeaderRule] {
+        &self.response_headers
+    }
+
+    pub fn core_validator(&self) -> Option<&dyn JwsVerifier> {
+        self.core_validator.as_deref()
+    }
+
+    pub fn client_url_signing(&self) -> Option<&UrlSigningConfig> {
+        self.client_url_signing.as_ref()
+    }
+
+    pub fn response(&self, purpose: &str) -> Option<&ResponseTemplate> {
+        self.responses.get(purpose)
+    }
+
+    pub fn faults(&self) -> &[Fault] {
+        &self.faults
+    }
+
+    pub fn recording_capacity(&self) -> usize {
+        self.recording_capacity
+    }
+
+    pub fn crash_report_dir(&self) -> &Path {
+        &self.crash_report_dir
+    }
+
+    pub fn admin_tokens(&self) -> &[AdminToken] {
+        &self.admin_tokens
+    }
+
+    pub fn has_authenticators(&self) -> bool {
+        !self.authenticators.is_empty()
+    }
+
+    // Name of the key validator_for selects, for key usage statistics
+    pub fn validator_id(&self, issuer: Option<&str>) -> &str {
+        match issuer {
+            Some(issuer) if self.authenticators.contains_key(issuer) => issuer,
+            _ => "signature_pubkey",
+        }
+    }
+
+    // Every key auth results are decrypted or verified with
+    pub fn key_ids(&self) -> Vec<(KeyKind, String)> {
+        let decryption = self
+            .decrypters
+            .iter()
+            .map(|(kid, _)| (KeyKind::Decryption, kid.clone()));
+        let verification = std::iter::once("signature_pubkey".to_string())
+            .chain(self.authenticators.keys().cloned())
+            .map(|id| (KeyKind::Verification, id));
+        decryption.chain(verification).collect()
+    }
+
+    // The key of the authenticator named by issuer, falling back to signature_pubkey
+    pub fn validator_for(&self, issuer: Option<&str>) -> &dyn JwsVerifier {
+        issuer
+            .and_then(|issuer| self.authenticators.get(issuer))
+            .map(|validator| validator.as_ref())
+            .unwrap_or_else(|| self.validator())
+    }
+
+    pub fn case_linking(&self) -> Option<&CaseLinkingConfig> {
+        self.case_linking.as_ref()
+    }
+
+    pub fn on_invalid_auth_result(&self) -> InvalidAuthResultPolicy {
+        self.on_invalid_auth_result
+    }
+
+    pub fn admin_signer(&self) -> Option<(&str, &dyn JwsSigner)> {
+        self.admin_signer
+            .as_ref()
+            .map(|(kid, signer)| (kid.as_str(), signer.as_ref()))
+    }
+
+    pub fn clock_skew(&self) -> i64 {
+        self.clock_skew
+    }
+
+    pub fn config_reload(&self) -> bool {
+        self.config_reload
+    }
+
+    pub fn ui_post_bridge(&self) -> bool {
+        self.ui_post_bridge
+    }
+
+    pub fn assurance(&self) -> Option<&AssuranceConfig> {
+        self.assurance.as_ref()
+    }
+
+    pub fn check_internal_url(&self) -> bool {
+        self.check_internal_url
+    }
+
+    pub fn session_expiry(&self) -> Option<&SessionExpiryConfig> {
+        self.session_expiry.as_ref()
+    }
+
+    pub fn purpose_names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.purposes.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    // Any purpose is allowed until purposes are configured
+    pub fn allows_purpose(&self, purpose: &str) -> bool {
+        self.purposes.is_empty() || self.purposes.contains_key(purpose)
+    }
+
+    pub fn purpose(&self, purpose: &str) -> Option<&PurposeConfig> {
+        self.purposes.get(purpose)
+    }
+
+    pub fn claim_validation(&self) -> Option<&ClaimValidationConfig> {
+        self.claim_validation.as_ref()
+    }
+
+    pub fn key_rotation(&self) -> Option<&KeyRotationConfig> {
+        self.key_rotation.as_ref()
+    }
+
+    pub fn expected_issuer(&self) -> Option<&str> {
+        self.expected_issuer.as_deref()
+    }
+
+    pub fn expected_audience(&self) -> Option<&str> {
+        self.expected_audience.as_deref()
+    }
+
+    pub fn replay_protection(&self) -> Option<&ReplayProtectionConfig> {
+        self.replay_protection.as_ref()
+    }
+
+    pub fn client(&self) -> Option<&ClientConfig> {
+        self.client.as_ref()
+    }
+
+    pub fn connection(&self) -> &ConnectionConfig {
+        &self.connection
+    }
+
+    pub fn on_duplicate_delivery(&self) -> DuplicateDeliveryPolicy {
+        self.on_duplicate_delivery
+    }
+
+    pub fn cache_max_age(&self) -> u64 {
+        self.cache_max_age
+    }
+
+    pub fn client_url_handles(&self) -> Option<u64> {
+        self.client_url_handles
+    }
+
+    pub fn attribute_formats(&self) -> &HashMap<String, AttributeFormat> {
+        &self.attribute_formats
+    }
+
+    pub fn admin_oidc(&self) -> Option<&OidcConfig> {
+        self.admin_oidc.as_ref()
+    }
+
+    pub fn webhook_format(&self) -> WebhookFormat {
+        self.webhook_format
+    }
+
+    pub fn retry_queue(&self) -> Option<&RetryConfig> {
+        self.retry_queue.as_ref()
+    }
+
+    pub fn core_client_identity(&self) -> Option<&ClientIdentityConfig> {
+        self.core_client_identity.as_ref()
+    }
+
+    #[cfg(feature = "grpc")]
+    pub fn grpc_address(&self) -> Option<SocketAddr> {
+        self.grpc_address
+    }
+
+    #[cfg(feature = "amqp")]
+    pub fn amqp(&self) -> Option<&AmqpConfig> {
+        self.amqp.as_ref()
+    }
+
+    #[cfg(feature = "nats")]
+    pub fn nats(&self) -> Option<&NatsConfig> {
+        self.nats.as_ref()
+    }
+
+    #[cfg(feature = "outbox")]
+    pub fn outbox(&self) -> Option<&OutboxConfig> {
+        self.outbox.as_ref()
+    }
+
+    #[cfg(feature = "kafka")]
+    pub fn kafka(&self) -> Option<&KafkaConfig> {
+        self.kafka.as_ref()
+    }
+
+    #[cfg(feature = "ws")]
+    pub fn events_address(&self) -> Option<SocketAddr> {
+        self.events_address
+    }
+
+    // The transports start_communication requests are accepted on
+    pub fn channels(&self) -> Vec<&'static str> {
+        let channels = [
+            Some("http"),
+            #[cfg(feature = "grpc")]
+            self.grpc_address.map(|_| "grpc"),
+            #[cfg(feature = "amqp")]
+            self.amqp.as_ref().map(|_| "amqp"),
+            #[cfg(feature = "nats")]
+            self.nats.as_ref().map(|_| "nats"),
+        ];
+        channels.iter().flatten().copied().collect()
+    }
 }
@@ -0,0 +1,92 @@
+use std::convert::Infallible;
+
+use rocket::{
+    request::{FromRequest, Outcome},
+    Request,
+};
+
+use serde::Deserialize;
+
+use crate::{config::Config, Error};
+
+pub const SIGNATURE_HEADER: &str = "X-Core-Signature";
+
+// The Rocket version used can't request client certificates, so mTLS is
+// terminated by a proxy in front, which passes on the verified identity
+#[derive(Deserialize, Debug, Clone)]
+pub struct ClientIdentityConfig {
+    // Header the proxy puts the client certificate's subject in, replacing
+    // any such header sent by the client
+    header: String,
+    // Subjects the core may present
+    allowed: Vec<String>,
+}
+
+// Client certificate subject of the caller, as passed on by the proxy
+pub struct CoreIdentity(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for CoreIdentity {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let identity = crate::reload::current(request)
+            .and_then(|config| config.core_client_identity())
+            .and_then(|identity| request.headers().get_one(&identity.header))
+            .map(|value| value.to_string());
+        Outcome::Success(CoreIdentity(identity))
+    }
+}
+
+impl CoreIdentity {
+    pub fn verify(&self, config: &Config) -> Result<(), Error> {
+        let identity = match config.core_client_identity() {
+            Some(identity) => identity,
+            None => return Ok(()),
+        };
+
+        match &self.0 {
+            Some(subject) if identity.allowed.iter().any(|allowed| allowed == subject) => Ok(()),
+            _ => Err(Error::UntrustedClient),
+        }
+    }
+}
+
+// Compact JWS from the core, whose payload is the request body
+pub struct CoreSignature(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for CoreSignature {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(CoreSignature(
+            request
+                .headers()
+                .get_one(SIGNATURE_HEADER)
+                .map(|value| value.to_string()),
+        ))
+    }
+}
+
+impl CoreSignature {
+    // The payload is compared as JSON, so differences in formatting between
+    // the signed payload and the body are allowed
+    pub fn verify(&self, config: &Config, body: &serde_json::Value) -> Result<(), Error> {
+        let validator = match config.core_validator() {
+            Some(validator) => validator,
+            None => return Ok(()),
+        };
+
+        let signature = self.0.as_ref().ok_or(Error::InvalidSignature)?;
+        let (payload, _) = josekit::jws::deserialize_compact(signature, validator)
+            .map_err(|_| Error::InvalidSignature)?;
+        let payload: serde_json::Value =
+            serde_json::from_slice(&payload).map_err(|_| Error::InvalidSignature)?;
+
+        if &payload != body {
+            return Err(Error::InvalidSignature);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,203 @@
+use std::{
+    backtrace::Backtrace,
+    cell::RefCell,
+    collections::BTreeMap,
+    future::Future,
+    panic::AssertUnwindSafe,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+    task::{Context, Poll},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rocket::{
+    catch,
+    http::Status,
+    route::{self, Handler},
+    serde::json::Json,
+    Data, Request, Route,
+};
+use serde::Serialize;
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::session::generate_id;
+
+// Headers that may carry credentials, never written to crash reports
+const REDACTED_HEADERS: &[&str] = &["authorization", "cookie", "x-core-signature", "x-signature"];
+
+static PANICS: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Serialize)]
+struct Incident {
+    incident_id: String,
+    occurred_at: u64,
+    message: String,
+    location: Option<String>,
+    backtrace: String,
+}
+
+#[derive(Serialize)]
+struct CrashReport<'a> {
+    #[serde(flatten)]
+    incident: &'a Incident,
+    method: String,
+    path: String,
+    route: Option<String>,
+    headers: BTreeMap<String, String>,
+}
+
+thread_local! {
+    // Handoff from the panic hook to the CatchPanic poll that caught the
+    // panic, which happens synchronously on the same thread
+    static PENDING: RefCell<Option<Incident>> = RefCell::new(None);
+}
+
+// The incident of the panic in the handler of this request, if any
+struct RequestIncident(Option<Incident>);
+
+// Polls a handler future, turning a panic into the incident the panic hook
+// recorded for it
+struct CatchPanic<'r, T> {
+    inner: Pin<Box<dyn Future<Output = T> + Send + 'r>>,
+}
+
+impl<'r, T> Future for CatchPanic<'r, T> {
+    type Output = Result<T, Option<Incident>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let inner = &mut self.inner;
+        match std::panic::catch_unwind(AssertUnwindSafe(|| inner.as_mut().poll(cx))) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(_) => Poll::Ready(Err(PENDING.with(|pending| pending.borrow_mut().take()))),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct IncidentHandler(Box<dyn Handler>);
+
+#[rocket::async_trait]
+impl Handler for IncidentHandler {
+    async fn handle<'r>(&self, request: &'r Request<'_>, data: Data<'r>) -> route::Outcome<'r> {
+        let handled = CatchPanic {
+            inner: self.0.handle(request, data),
+        };
+        match handled.await {
+            Ok(outcome) => outcome,
+            Err(incident) => {
+                request.local_cache(|| RequestIncident(incident));
+                route::Outcome::Failure(Status::InternalServerError)
+            }
+        }
+    }
+}
+
+// Wraps the handlers of routes so that panics in them are reported with
+// the details of the request they occurred in
+pub fn catch_panics(routes: Vec<Route>) -> Vec<Route> {
+    routes
+        .into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(IncidentHandler(route.handler));
+            route
+        })
+        .collect()
+}
+
+pub fn panic_count() -> u64 {
+    PANICS.load(Ordering::Relaxed)
+}
+
+fn write_report(dir: &Path, incident_id: &str, report: &impl Serialize) {
+    let result = std::fs::create_dir_all(dir).and_then(|_| {
+        let report = serde_json::to_vec_pretty(report)?;
+        std::fs::write(dir.join(format!("{}.json", incident_id)), report)
+    });
+    if let Err(e) = result {
+        warn!("Failed to write crash report {}: {}", incident_id, e);
+    }
+}
+
+pub fn install_hook(dir: PathBuf) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        PANICS.fetch_add(1, Ordering::Relaxed);
+
+        let message = match info.payload().downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => match info.payload().downcast_ref::<String>() {
+                Some(message) => message.clone(),
+                None => "Unknown panic".to_string(),
+            },
+        };
+        let incident = Incident {
+            incident_id: generate_id(),
+            occurred_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|t| t.as_secs())
+                .unwrap_or_default(),
+            message,
+            location: info.location().map(|location| location.to_string()),
+            backtrace: Backtrace::force_capture().to_string(),
+        };
+        info!("Panic recorded as incident {}", incident.incident_id);
+
+        // Write the bare report right away, in case this panic isn't
+        // coming from a request handler
+        write_report(&dir, &incident.incident_id, &incident);
+        PENDING.with(|pending| *pending.borrow_mut() = Some(incident));
+    }));
+}
+
+pub struct CrashReportDir(pub PathBuf);
+
+#[catch(500)]
+pub fn internal_error(request: &Request<'_>) -> (Status, Json<serde_json::Value>) {
+    let incident = match &request.local_cache(|| RequestIncident(None)).0 {
+        Some(incident) => incident,
+        None => {
+            return (
+                Status::InternalServerError,
+                Json(json!({"error": "internal_error"})),
+            )
+        }
+    };
+
+    if let Some(dir) = request.rocket().state::<CrashReportDir>() {
+        let report = CrashReport {
+            incident,
+            method: request.method().to_string(),
+            // Queries can carry auth results, so leave them out
+            path: request.uri().path().to_string(),
+            route: request
+                .route()
+                .map(|route| format!("{} {}", route.method, route.uri)),
+            headers: request
+                .headers()
+                .iter()
+                .map(|header| {
+                    let name = header.name().as_str().to_ascii_lowercase();
+                    let value = if REDACTED_HEADERS.contains(&name.as_str()) {
+                        "[redacted]".to_string()
+                    } else {
+                        header.value().to_string()
+                    };
+                    (name, value)
+                })
+                .collect(),
+        };
+        write_report(&dir.0, &incident.incident_id, &report);
+    }
+
+    (
+        Status::InternalServerError,
+        Json(json!({
+            "error": "internal_error",
+            "incident_id": incident.incident_id,
+        })),
+    )
+}
@@ -0,0 +1,100 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rocket::{get, response::content::Html, State};
+
+use crate::{
+    access::Viewer, config::Config, faults::FaultInjector, session::SessionStore, stats::RouteStats,
+};
+
+const REFRESH_SECONDS: u32 = 5;
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn timestamp(at: SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+#[get("/admin/dashboard?<tag>")]
+pub fn dashboard(
+    _viewer: Viewer,
+    tag: Option<&str>,
+    sessions: &State<SessionStore>,
+    stats: &State<RouteStats>,
+    faults: &State<FaultInjector>,
+    config: &Config,
+) -> Html<String> {
+    let mut page = format!(
+        "<!DOCTYPE html><html><head><title>comm-test dashboard</title>\
+         <meta http-equiv=\"refresh\" content=\"{}\"></head><body>\
+         <h1>comm-test dashboard</h1>",
+        REFRESH_SECONDS
+    );
+
+    let sessions: Vec<_> = sessions
+        .list()
+        .into_iter()
+        .filter(|session| session.tagged(tag))
+        .collect();
+    page.push_str(&format!(
+        "<h2>Sessions ({})</h2><table><tr><th>Id</th><th>Purpose</th>\
+         <th>Started</th><th>Attributes received</th><th>Tags</th></tr>",
+        sessions.len()
+    ));
+    for session in sessions {
+        let tags: Vec<_> = session.tags().iter().map(String::as_str).collect();
+        page.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(session.id()),
+            escape(session.purpose()),
+            timestamp(session.created_at()),
+            session.auth_result().is_some(),
+            escape(&tags.join(", "))
+        ));
+    }
+    page.push_str("</table>");
+
+    page.push_str("<h2>Faults in effect</h2><ul>");
+    for fault in faults.active(config) {
+        page.push_str(&format!("<li>{}</li>", escape(&format!("{:?}", fault))));
+    }
+    page.push_str("</ul>");
+
+    page.push_str(
+        "<h2>Routes</h2><table><tr><th>Route</th><th>Requests</th>\
+         <th>4xx</th><th>5xx</th></tr>",
+    );
+    for (route, counts) in stats.routes() {
+        page.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(&route),
+            counts.total,
+            counts.client_errors,
+            counts.server_errors
+        ));
+    }
+    page.push_str("</table>");
+
+    page.push_str(
+        "<h2>Recent errors</h2><table><tr><th>At</th><th>Request</th><th>Status</th></tr>",
+    );
+    for error in stats.recent_errors().iter().rev() {
+        page.push_str(&format!(
+            "<tr><td>{}</td><td>{} {}</td><td>{}</td></tr>",
+            timestamp(error.at),
+            escape(&error.method),
+            escape(&error.uri),
+            error.status
+        ));
+    }
+    page.push_str(&format!(
+        "</table><p>Handler panics: {}</p></body></html>",
+        crate::crash::panic_count()
+    ));
+
+    Html(page)
+}
@@ -0,0 +1,61 @@
+use std::{
+    convert::Infallible,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use rocket::{
+    request::{FromRequest, Outcome},
+    Request,
+};
+
+use crate::Error;
+
+// Absolute deadline, in (fractional) seconds since the unix epoch
+pub const DEADLINE_HEADER: &str = "X-Request-Deadline";
+// Relative timeout, in milliseconds from receiving the request
+pub const TIMEOUT_HEADER: &str = "X-Request-Timeout";
+
+pub struct Deadline(Option<Instant>);
+
+fn parse_deadline(value: &str) -> Option<Instant> {
+    let seconds = value.parse::<f64>().ok()?;
+    // Guard against values from_secs_f64 would panic on
+    if !seconds.is_finite() || seconds < 0.0 || seconds > u32::MAX as f64 {
+        return None;
+    }
+    let deadline = UNIX_EPOCH + Duration::from_secs_f64(seconds);
+    let now = SystemTime::now();
+    match deadline.duration_since(now) {
+        Ok(remaining) => Instant::now().checked_add(remaining),
+        // Already passed
+        Err(_) => Some(Instant::now()),
+    }
+}
+
+// Timeouts too large to represent are ignored, like malformed ones
+fn parse_timeout(value: &str) -> Option<Instant> {
+    Instant::now().checked_add(Duration::from_millis(value.parse().ok()?))
+}
+
+impl Deadline {
+    pub fn check(&self) -> Result<(), Error> {
+        match self.0 {
+            Some(deadline) if Instant::now() >= deadline => Err(Error::DeadlineExceeded),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Deadline {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let headers = request.headers();
+        let deadline = headers
+            .get_one(DEADLINE_HEADER)
+            .and_then(parse_deadline)
+            .or_else(|| headers.get_one(TIMEOUT_HEADER).and_then(parse_timeout));
+        Outcome::Success(Deadline(deadline))
+    }
+}
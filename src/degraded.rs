@@ -0,0 +1,105 @@
+use rocket::{
+    catch, catchers, figment::Figment, get, http::Status, post, routes, serde::json::Json, Build,
+    Rocket,
+};
+use rocket_dyn_templates::Template;
+use serde_json::{json, Value};
+use tracing::warn;
+
+// Whether to keep serving diagnostics when the configuration or keys fail
+// to load, rather than refusing to start
+pub fn enabled(figment: &Figment) -> bool {
+    figment
+        .extract_inner::<bool>("degraded_mode")
+        .unwrap_or(false)
+}
+
+fn unavailable() -> (Status, Json<Value>) {
+    (
+        Status::ServiceUnavailable,
+        Json(json!({
+            "error": "keys_unavailable",
+            "description": "Configuration or keys failed to load, crypto is unavailable",
+        })),
+    )
+}
+
+#[get("/health")]
+fn health() -> (Status, Json<Value>) {
+    (
+        Status::ServiceUnavailable,
+        Json(json!({ "status": "degraded", "keys": "unavailable" })),
+    )
+}
+
+#[get("/health/live")]
+fn live() -> &'static str {
+    "ok"
+}
+
+#[get("/health/ready")]
+fn ready() -> (Status, Json<Value>) {
+    unavailable()
+}
+
+// Also mounted outside of degraded mode
+#[get("/version")]
+pub fn version() -> Json<Value> {
+    Json(json!({ "version": env!("CARGO_PKG_VERSION") }))
+}
+
+#[get("/ui")]
+fn ui() -> Template {
+    Template::render("ui", json!({ "degraded": true }))
+}
+
+#[post("/start_communication")]
+fn start() -> (Status, Json<Value>) {
+    unavailable()
+}
+
+#[post("/auth_result")]
+fn attr_url() -> (Status, Json<Value>) {
+    unavailable()
+}
+
+#[post("/auth_results/batch")]
+fn attr_url_batch() -> (Status, Json<Value>) {
+    unavailable()
+}
+
+#[post("/provider/auth_result")]
+fn provider_auth_result() -> (Status, Json<Value>) {
+    unavailable()
+}
+
+#[get("/.well-known/jwks.json")]
+fn jwks() -> (Status, Json<Value>) {
+    unavailable()
+}
+
+#[catch(404)]
+fn not_found() -> (Status, Json<Value>) {
+    unavailable()
+}
+
+pub fn build(base: Rocket<Build>) -> Rocket<Build> {
+    warn!("Failed to load configuration, starting in degraded mode");
+    base.mount(
+        "/",
+        routes![
+            health,
+            live,
+            ready,
+            version,
+            ui,
+            start,
+            attr_url,
+            attr_url_batch,
+            provider_auth_result,
+            jwks
+        ],
+    )
+    .register("/", catchers![not_found])
+    .attach(Template::fairing())
+}
@@ -0,0 +1,157 @@
+use std::{error::Error as StdError, fmt::Display, time::Duration};
+
+use josekit::{jwe::RSA_OAEP, jws::RS256, JoseError};
+use rocket::figment::Figment;
+use serde_json::{json, Value};
+
+#[derive(Debug)]
+pub enum Error {
+    Jose(JoseError),
+    Http(reqwest::Error),
+    Launch(rocket::Error),
+    Io(std::io::Error),
+}
+
+impl From<JoseError> for Error {
+    fn from(e: JoseError) -> Error {
+        Error::Jose(e)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Error {
+        Error::Http(e)
+    }
+}
+
+impl From<rocket::Error> for Error {
+    fn from(e: rocket::Error) -> Error {
+        Error::Launch(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Jose(e) => e.fmt(f),
+            Error::Http(e) => e.fmt(f),
+            Error::Launch(e) => e.fmt(f),
+            Error::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Jose(e) => Some(e),
+            Error::Http(e) => Some(e),
+            Error::Launch(e) => Some(e),
+            Error::Io(e) => Some(e),
+        }
+    }
+}
+
+// Configuration with freshly generated keys, where the attribute provider
+// simulator produces auth results the plugin itself can decrypt
+fn demo_figment(port: u16) -> Result<Figment, Error> {
+    let sign_key = RS256.generate_key_pair(2048)?;
+    let enc_key = RSA_OAEP.generate_key_pair(2048)?;
+    let url = format!("http://localhost:{}", port);
+    let key = |pem: Vec<u8>| json!({ "type": "RSA", "key": String::from_utf8_lossy(&pem) });
+
+    Ok(crate::config::figment()
+        .merge(("port", port))
+        .merge(("server_url", &url))
+        .merge(("internal_url", &url))
+        .merge(("signature_pubkey", key(sign_key.to_pem_public_key())))
+        .merge(("decryption_privkey", key(enc_key.to_pem_private_key())))
+        .merge((
+            "attribute_provider",
+            json!({
+                "signing_privkey": key(sign_key.to_pem_private_key()),
+                "encryption_pubkey": key(enc_key.to_pem_public_key()),
+            }),
+        )))
+}
+
+async fn wait_for_enter(step: &str) -> Result<(), Error> {
+    println!("\n{}\nPress enter to continue...", step);
+    rocket::tokio::task::spawn_blocking(|| {
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line).map(|_| ())
+    })
+    .await
+    .map_err(|e| Error::Io(e.into()))??;
+    Ok(())
+}
+
+async fn walk_through(url: &str) -> Result<(), Error> {
+    let client = reqwest::Client::new();
+
+    // Wait for the server to come up
+    for _ in 0..50 {
+        if client.get(format!("{}/metadata", url)).send().await.is_ok() {
+            break;
+        }
+        rocket::tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    wait_for_enter(
+        "Step 1: an authentication plugin delivers attributes, simulated by \
+         asking the attribute provider for a signed and encrypted auth result.",
+    )
+    .await?;
+    let auth_result = client
+        .post(format!("{}/provider/auth_result", url))
+        .json(&json!({}))
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    println!("Received auth result: {}", auth_result);
+
+    wait_for_enter(
+        "Step 2: the core starts communication with that auth result, as a \
+         core would after authentication.",
+    )
+    .await?;
+    let response: Value = client
+        .post(format!("{}/start_communication", url))
+        .json(&json!({ "purpose": "demo", "auth_result": auth_result }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    println!("Response: {}", response);
+
+    println!(
+        "\nStep 3: open the client url to see the disclosed attributes:\n  {}\n\
+         The operator dashboard is at:\n  {}/admin/dashboard\n\
+         Press ctrl-c to stop the demo.",
+        response["client_url"].as_str().unwrap_or_default(),
+        url
+    );
+    Ok(())
+}
+
+pub async fn run() -> Result<(), Error> {
+    let port = crate::config::figment()
+        .extract_inner::<u16>("port")
+        .unwrap_or(8000);
+    let url = format!("http://localhost:{}", port);
+    println!("Starting demo plugin with ephemeral keys on {}", url);
+
+    let server = rocket::tokio::spawn(crate::rocket(demo_figment(port)?).launch());
+    walk_through(&url).await?;
+    server.await.map_err(|e| Error::Io(e.into()))??;
+    Ok(())
+}
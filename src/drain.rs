@@ -0,0 +1,145 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    Data, Orbit, Request, Response, Rocket,
+};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::events::Events;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+struct Inner {
+    draining: AtomicBool,
+    in_flight: AtomicUsize,
+}
+
+// Work that has yet to finish before the plugin can stop cleanly
+#[derive(Serialize)]
+pub struct Remaining {
+    pub in_flight_requests: usize,
+    pub event_streams: usize,
+    pub pending_callbacks: usize,
+}
+
+impl Remaining {
+    fn is_empty(&self) -> bool {
+        self.in_flight_requests == 0 && self.event_streams == 0 && self.pending_callbacks == 0
+    }
+}
+
+// Tracks whether shutdown has started and what is still in progress, shared
+// between the fairing, managed state and whatever makes outgoing callbacks
+#[derive(Clone)]
+pub struct Drain {
+    inner: Arc<Inner>,
+    events: Events,
+    callbacks: Arc<AtomicUsize>,
+}
+
+impl Drain {
+    pub fn new(events: Events) -> Drain {
+        Drain {
+            inner: Arc::default(),
+            events,
+            callbacks: Arc::default(),
+        }
+    }
+
+    // Counter to keep up to date with the number of outgoing calls in progress
+    pub fn callbacks(&self) -> Arc<AtomicUsize> {
+        self.callbacks.clone()
+    }
+
+    pub fn draining(&self) -> bool {
+        self.inner.draining.load(Ordering::Relaxed)
+    }
+
+    pub fn remaining(&self) -> Remaining {
+        Remaining {
+            in_flight_requests: self.inner.in_flight.load(Ordering::Relaxed),
+            event_streams: self.events.subscriber_count(),
+            pending_callbacks: self.callbacks.load(Ordering::Relaxed),
+        }
+    }
+}
+
+async fn log_progress(drain: Drain) {
+    loop {
+        let remaining = drain.remaining();
+        if remaining.is_empty() {
+            info!("Drained, nothing left in progress");
+            return;
+        }
+        info!(
+            in_flight_requests = remaining.in_flight_requests,
+            event_streams = remaining.event_streams,
+            pending_callbacks = remaining.pending_callbacks,
+            "Draining"
+        );
+        rocket::tokio::time::sleep(PROGRESS_INTERVAL).await;
+    }
+}
+
+#[cfg(unix)]
+async fn terminated() {
+    use rocket::tokio::signal::unix::{signal, SignalKind};
+
+    match signal(SignalKind::terminate()) {
+        Ok(mut terminations) => {
+            terminations.recv().await;
+        }
+        Err(e) => {
+            warn!("Failed to listen for SIGTERM: {}", e);
+            rocket::futures::future::pending::<()>().await;
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn terminated() {
+    rocket::futures::future::pending::<()>().await;
+}
+
+#[rocket::async_trait]
+impl Fairing for Drain {
+    fn info(&self) -> Info {
+        Info {
+            name: "Connection draining",
+            kind: Kind::Liftoff | Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let drain = self.clone();
+        let shutdown = rocket.shutdown();
+        // Rocket only handles ctrl-c itself, SIGTERM as sent by orchestrators
+        // triggers the same graceful shutdown here
+        rocket::tokio::spawn(async move {
+            rocket::tokio::select! {
+                _ = rocket::tokio::signal::ctrl_c() => {}
+                _ = terminated() => shutdown.notify(),
+            }
+            info!("Shutting down, draining connections");
+            drain.inner.draining.store(true, Ordering::Relaxed);
+            log_progress(drain).await;
+        });
+    }
+
+    async fn on_request(&self, _request: &mut Request<'_>, _data: &mut Data<'_>) {
+        self.inner.in_flight.fetch_add(1, Ordering::Relaxed);
+    }
+
+    async fn on_response<'r>(&self, _request: &'r Request<'_>, _response: &mut Response<'r>) {
+        self.inner.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,66 @@
+use std::{
+    collections::{hash_map::DefaultHasher, VecDeque},
+    hash::{Hash, Hasher},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use serde_json::json;
+use tracing::info;
+
+use crate::{config::Config, events::Events};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DuplicateDetectionConfig {
+    window: u64,
+    #[serde(default)]
+    reject: bool,
+}
+
+// Settings are taken from the configuration of each request, so they follow
+// reloads while the bodies seen so far are kept
+#[derive(Default)]
+pub struct DuplicateDetector {
+    seen: Mutex<VecDeque<(Instant, u64)>>,
+}
+
+impl DuplicateDetector {
+    // Returns whether the body should be rejected as duplicate
+    pub fn check(&self, body: &[u8], config: &Config, events: &Events) -> bool {
+        let config = match config.duplicate_detection() {
+            Some(config) => config,
+            None => return false,
+        };
+
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let now = Instant::now();
+        let window = Duration::from_secs(config.window);
+        let mut seen = self.seen.lock().unwrap();
+        while let Some((at, _)) = seen.front() {
+            if now.duration_since(*at) > window {
+                seen.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        let duplicate = seen.iter().any(|(_, seen_hash)| *seen_hash == hash);
+        seen.push_back((now, hash));
+
+        if duplicate {
+            info!(
+                "Duplicate communication request received within {} seconds",
+                config.window
+            );
+            events.emit(
+                "duplicate_auth_result",
+                json!({ "window": config.window, "rejected": config.reject }),
+            );
+        }
+        duplicate && config.reject
+    }
+}
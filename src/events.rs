@@ -0,0 +1,87 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rocket::tokio::sync::broadcast;
+use serde::Serialize;
+use serde_json::Value;
+
+// Each subscriber can fall this many events behind, beyond that its oldest
+// unread events are dropped, so slow subscribers never hold up emitting
+const CAPACITY: usize = 256;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Event {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    at: u64,
+    #[serde(flatten)]
+    data: Value,
+}
+
+#[cfg(feature = "kafka")]
+impl Event {
+    pub fn kind(&self) -> &'static str {
+        self.kind
+    }
+
+    pub fn data(&self) -> &Value {
+        &self.data
+    }
+}
+
+// Cheaply cloneable broadcast of things happening in the plugin, for live
+// observers. Emitting without subscribers is a no-op.
+#[derive(Clone)]
+pub struct Events {
+    sender: broadcast::Sender<Event>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Default for Events {
+    fn default() -> Events {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        Events {
+            sender,
+            dropped: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+impl Events {
+    pub fn emit(&self, kind: &'static str, data: Value) {
+        let event = Event {
+            kind,
+            at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|t| t.as_secs())
+                .unwrap_or_default(),
+            data,
+        };
+        let _ = self.sender.send(event);
+    }
+
+    #[cfg(any(feature = "ws", feature = "kafka"))]
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    // Called by subscribers that fell behind with the number of events missed
+    #[cfg(any(feature = "ws", feature = "kafka"))]
+    pub fn record_dropped(&self, count: u64) {
+        self.dropped.fetch_add(count, Ordering::Relaxed);
+    }
+
+    // Events dropped for slow subscribers since startup
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,136 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rocket::{fairing::AdHoc, Build, Rocket};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::{
+    cloudevents::Envelope,
+    drain::Drain,
+    reload::LiveConfig,
+    retry::RetryQueue,
+    session::{generate_id, Session, SessionStore},
+};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SessionExpiryConfig {
+    // Seconds after starting a session is removed
+    timeout: u64,
+    // Seconds after receiving them attribute values are wiped, keeping the
+    // session itself until timeout
+    attribute_lifetime: Option<u64>,
+    // Notified of sessions expiring without having received an auth result
+    webhook_url: Option<String>,
+}
+
+async fn notify(
+    client: &reqwest::Client,
+    url: &str,
+    envelope: &Envelope,
+    retries: &RetryQueue,
+    session: &Session,
+    callbacks: &AtomicUsize,
+) {
+    let event = json!({
+        "event": "session_abandoned",
+        "session_id": session.id(),
+        "purpose": session.purpose(),
+        "duration": session.age().as_secs(),
+        "last_state": session.status(),
+    });
+    callbacks.fetch_add(1, Ordering::Relaxed);
+    let request = envelope.wrap(
+        client.post(url),
+        &generate_id(),
+        "session_abandoned",
+        Some(session.id()),
+        &event,
+    );
+    let result = retries.send(client, request).await;
+    callbacks.fetch_sub(1, Ordering::Relaxed);
+    if let Err(e) = result {
+        warn!(
+            "Failed to report abandoned session {} to {}: {}",
+            session.id(),
+            url,
+            e
+        );
+    }
+}
+
+async fn expire(
+    config: SessionExpiryConfig,
+    envelope: Envelope,
+    retries: RetryQueue,
+    sessions: SessionStore,
+    callbacks: Arc<AtomicUsize>,
+) {
+    let client = reqwest::Client::new();
+    let timeout = Duration::from_secs(config.timeout);
+    loop {
+        rocket::tokio::time::sleep(CHECK_INTERVAL).await;
+        if let Some(lifetime) = config.attribute_lifetime {
+            for id in sessions.wipe_attributes(Duration::from_secs(lifetime)) {
+                info!("Wiped attributes of session {}", id);
+            }
+        }
+        for session in sessions.remove_expired(timeout) {
+            if session.auth_result().is_some() {
+                info!("Session {} expired", session.id());
+                sessions.events().emit(
+                    "session_completed",
+                    json!({ "session_id": session.id(), "last_state": session.status() }),
+                );
+                continue;
+            }
+
+            info!(
+                "Session {} abandoned after {} seconds",
+                session.id(),
+                session.age().as_secs()
+            );
+            sessions.events().emit(
+                "session_abandoned",
+                json!({ "session_id": session.id(), "last_state": session.status() }),
+            );
+            if let Some(url) = &config.webhook_url {
+                notify(&client, url, &envelope, &retries, &session, &callbacks).await;
+            }
+        }
+    }
+}
+
+pub fn attach(base: Rocket<Build>) -> Rocket<Build> {
+    base.attach(AdHoc::on_liftoff("Session expiry", |rocket| {
+        Box::pin(async move {
+            let live = match rocket.state::<LiveConfig>() {
+                Some(live) => live.current(),
+                None => return,
+            };
+            let config = match live.session_expiry().cloned() {
+                Some(config) => config,
+                None => return,
+            };
+            let envelope = Envelope::new(&live);
+            let sessions = match rocket.state::<SessionStore>() {
+                Some(sessions) => sessions.clone(),
+                None => return,
+            };
+            let callbacks = rocket
+                .state::<Drain>()
+                .map(Drain::callbacks)
+                .unwrap_or_default();
+            let retries = rocket.state::<RetryQueue>().cloned().unwrap_or_default();
+            rocket::tokio::spawn(expire(config, envelope, retries, sessions, callbacks));
+        })
+    }))
+}
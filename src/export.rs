@@ -0,0 +1,139 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rocket::{
+    futures::stream::{self, Stream},
+    get,
+    http::ContentType,
+    response::stream::TextStream,
+    State,
+};
+use serde::Serialize;
+
+use crate::{
+    access::Viewer,
+    session::{Session, SessionStore},
+};
+
+const CSV_HEADER: &str =
+    "session_id,purpose,status,created_at,auth_result_received_at,case_id,attributes,tags\n";
+
+// A session without attribute values, only the names of those received
+#[derive(Serialize)]
+struct ExportedSession {
+    session_id: String,
+    purpose: String,
+    status: &'static str,
+    created_at: u64,
+    auth_result_received_at: Option<u64>,
+    case_id: Option<String>,
+    attributes: Vec<String>,
+    tags: Vec<String>,
+}
+
+fn unix(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|t| t.as_secs())
+        .unwrap_or_default()
+}
+
+impl From<&Session> for ExportedSession {
+    fn from(session: &Session) -> ExportedSession {
+        let mut attributes: Vec<String> = session
+            .auth_result()
+            .and_then(|auth_result| auth_result.attributes.as_ref())
+            .map(|attributes| attributes.keys().cloned().collect())
+            .unwrap_or_default();
+        attributes.sort();
+        ExportedSession {
+            session_id: session.id().to_string(),
+            purpose: session.purpose().to_string(),
+            status: session.status(),
+            created_at: unix(session.created_at()),
+            auth_result_received_at: session.auth_result_received_at().map(unix),
+            case_id: session.case_id().map(|case_id| case_id.to_string()),
+            attributes,
+            tags: session.tags().iter().cloned().collect(),
+        }
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+impl ExportedSession {
+    fn csv_row(&self) -> String {
+        let fields = [
+            csv_field(&self.session_id),
+            csv_field(&self.purpose),
+            self.status.to_string(),
+            self.created_at.to_string(),
+            self.auth_result_received_at
+                .map(|at| at.to_string())
+                .unwrap_or_default(),
+            csv_field(self.case_id.as_deref().unwrap_or_default()),
+            csv_field(&self.attributes.join(";")),
+            csv_field(&self.tags.join(";")),
+        ];
+        format!("{}\n", fields.join(","))
+    }
+}
+
+// Sessions started within the time range, in unix seconds, for the purpose
+// and with the tag
+fn select(
+    sessions: &SessionStore,
+    from: Option<u64>,
+    to: Option<u64>,
+    purpose: Option<&str>,
+    tag: Option<&str>,
+) -> Vec<ExportedSession> {
+    sessions
+        .list()
+        .iter()
+        .filter(|session| session.tagged(tag))
+        .map(ExportedSession::from)
+        .filter(|session| from.map_or(true, |from| session.created_at >= from))
+        .filter(|session| to.map_or(true, |to| session.created_at < to))
+        .filter(|session| purpose.map_or(true, |purpose| session.purpose == purpose))
+        .collect()
+}
+
+#[get("/admin/export/sessions.csv?<from>&<to>&<purpose>&<tag>")]
+pub fn csv(
+    _viewer: Viewer,
+    from: Option<u64>,
+    to: Option<u64>,
+    purpose: Option<&str>,
+    tag: Option<&str>,
+    sessions: &State<SessionStore>,
+) -> (ContentType, TextStream<impl Stream<Item = String>>) {
+    let rows = select(sessions, from, to, purpose, tag)
+        .into_iter()
+        .map(|session| session.csv_row());
+    let lines = std::iter::once(CSV_HEADER.to_string()).chain(rows);
+    (ContentType::CSV, TextStream::from(stream::iter(lines)))
+}
+
+#[get("/admin/export/sessions.ndjson?<from>&<to>&<purpose>&<tag>")]
+pub fn ndjson(
+    _viewer: Viewer,
+    from: Option<u64>,
+    to: Option<u64>,
+    purpose: Option<&str>,
+    tag: Option<&str>,
+    sessions: &State<SessionStore>,
+) -> (ContentType, TextStream<impl Stream<Item = String>>) {
+    let lines = select(sessions, from, to, purpose, tag)
+        .into_iter()
+        .filter_map(|session| serde_json::to_string(&session).ok())
+        .map(|line| format!("{}\n", line));
+    (
+        ContentType::new("application", "x-ndjson"),
+        TextStream::from(stream::iter(lines)),
+    )
+}
@@ -0,0 +1,102 @@
+use std::{sync::RwLock, time::Duration};
+
+use rand::Rng;
+use rocket::{get, post, serde::json::Json, State};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    access::{Operator, Viewer},
+    config::Config,
+};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FaultKind {
+    Error,
+    Malformed,
+    Hang,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Fault {
+    kind: FaultKind,
+    // Percentage of start_communication requests affected
+    percentage: f64,
+    // Duration of hangs
+    #[serde(default)]
+    seconds: u64,
+}
+
+pub enum Injected {
+    Error,
+    Malformed,
+    Hang(Duration),
+}
+
+#[derive(Default)]
+struct Faults {
+    active: Vec<Fault>,
+    // The configured faults the active ones were last reset to
+    configured: Vec<Fault>,
+}
+
+// Faults set through the api stay in effect until the configured faults
+// change on reload
+#[derive(Default)]
+pub struct FaultInjector {
+    faults: RwLock<Faults>,
+}
+
+impl FaultInjector {
+    fn sync(&self, config: &Config) {
+        if self.faults.read().unwrap().configured == config.faults() {
+            return;
+        }
+        let mut faults = self.faults.write().unwrap();
+        faults.configured = config.faults().to_vec();
+        faults.active = faults.configured.clone();
+    }
+
+    pub fn active(&self, config: &Config) -> Vec<Fault> {
+        self.sync(config);
+        self.faults.read().unwrap().active.clone()
+    }
+
+    // Faults are tried in order, so their percentages add up
+    pub fn roll(&self, config: &Config) -> Option<Injected> {
+        self.sync(config);
+        let faults = self.faults.read().unwrap();
+        let roll = rand::thread_rng().gen_range(0.0..100.0);
+        let mut threshold = 0.0;
+        for fault in faults.active.iter() {
+            threshold += fault.percentage;
+            if roll < threshold {
+                info!("Injecting fault {:?}", fault);
+                return Some(match fault.kind {
+                    FaultKind::Error => Injected::Error,
+                    FaultKind::Malformed => Injected::Malformed,
+                    FaultKind::Hang => Injected::Hang(Duration::from_secs(fault.seconds)),
+                });
+            }
+        }
+        None
+    }
+}
+
+#[get("/internal/faults")]
+pub fn get(_viewer: Viewer, config: &Config, faults: &State<FaultInjector>) -> Json<Vec<Fault>> {
+    Json(faults.active(config))
+}
+
+#[post("/internal/faults", data = "<new_faults>")]
+pub fn set(
+    _operator: Operator,
+    new_faults: Json<Vec<Fault>>,
+    config: &Config,
+    faults: &State<FaultInjector>,
+) {
+    info!("Configured faults {:?}", new_faults);
+    faults.sync(config);
+    faults.faults.write().unwrap().active = new_faults.into_inner();
+}
@@ -0,0 +1,122 @@
+use rocket::{figment::Figment, get, serde::json::Json, State};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{access::Viewer, reload::LiveConfig};
+
+// Entries with names containing these are replaced by a hash of their value,
+// such that divergences remain visible without disclosing secrets
+const REDACTED_NAMES: &[&str] = &["key", "secret", "password", "token"];
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ConfigFingerprint {
+    fingerprint: String,
+    config: Value,
+}
+
+fn hash(value: &Value) -> String {
+    // serde_json keeps object keys sorted, so this is stable
+    hex::encode(Sha256::digest(value.to_string().as_bytes()))
+}
+
+fn redact(value: Value) -> Value {
+    match value {
+        Value::Object(entries) => Value::Object(
+            entries
+                .into_iter()
+                .map(|(name, value)| {
+                    let lower = name.to_lowercase();
+                    if REDACTED_NAMES
+                        .iter()
+                        .any(|redacted| lower.contains(redacted))
+                    {
+                        let redacted = Value::String(format!("sha256:{}", &hash(&value)[..16]));
+                        (name, redacted)
+                    } else {
+                        (name, redact(value))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.into_iter().map(redact).collect()),
+        value => value,
+    }
+}
+
+impl ConfigFingerprint {
+    pub fn compute(figment: &Figment) -> ConfigFingerprint {
+        let mut config = figment.extract::<Value>().unwrap_or(Value::Null);
+        // Leave out Rocket's own settings, such as workers and temp_dir,
+        // which depend on the host rather than on the plugin configuration
+        if let (Value::Object(entries), Ok(Value::Object(rocket))) =
+            (&mut config, serde_json::to_value(rocket::Config::default()))
+        {
+            entries.retain(|name, _| !rocket.contains_key(name));
+        }
+        let config = redact(config);
+        ConfigFingerprint {
+            fingerprint: hash(&config),
+            config,
+        }
+    }
+}
+
+#[get("/admin/config/fingerprint")]
+pub fn get(_viewer: Viewer, live: &State<LiveConfig>) -> Json<ConfigFingerprint> {
+    Json(live.fingerprint())
+}
+
+fn report_divergences(path: &str, a: &Value, b: &Value, divergences: &mut Vec<String>) {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (name, value) in a {
+                let path = format!("{}.{}", path, name);
+                match b.get(name) {
+                    Some(other) => report_divergences(&path, value, other, divergences),
+                    None => divergences.push(format!("{}: only in first instance", path)),
+                }
+            }
+            for name in b.keys().filter(|name| !a.contains_key(*name)) {
+                divergences.push(format!("{}.{}: only in second instance", path, name));
+            }
+        }
+        (a, b) if a != b => divergences.push(format!("{}: {} != {}", path, a, b)),
+        _ => {}
+    }
+}
+
+#[derive(Deserialize)]
+struct ConfigFingerprintResponse {
+    fingerprint: String,
+    config: Value,
+}
+
+async fn fetch(url: &str) -> Result<ConfigFingerprintResponse, reqwest::Error> {
+    reqwest::get(format!(
+        "{}/admin/config/fingerprint",
+        url.trim_end_matches('/')
+    ))
+    .await?
+    .error_for_status()?
+    .json()
+    .await
+}
+
+// Compares the effective configuration of two running instances, returning
+// whether they are equal
+pub async fn diff_config(first: &str, second: &str) -> Result<bool, reqwest::Error> {
+    let first = fetch(first).await?;
+    let second = fetch(second).await?;
+    if first.fingerprint == second.fingerprint {
+        println!("Configurations are identical ({})", first.fingerprint);
+        return Ok(true);
+    }
+
+    let mut divergences = vec![];
+    report_divergences("", &first.config, &second.config, &mut divergences);
+    for divergence in divergences {
+        println!("{}", divergence);
+    }
+    Ok(false)
+}
@@ -0,0 +1,248 @@
+use std::{collections::HashMap, convert::Infallible};
+
+use rocket::{
+    request::{FromRequest, Outcome},
+    Request,
+};
+use serde::Deserialize;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Locale {
+    En,
+    Nl,
+}
+
+impl Locale {
+    // First supported language in the Accept-Language header, ignoring
+    // quality values as browsers list languages in order of preference
+    fn negotiate(accept_language: &str) -> Option<Locale> {
+        accept_language.split(',').find_map(|range| {
+            let tag = range.split(';').next()?.trim();
+            let language = tag.split('-').next()?;
+            match language.to_ascii_lowercase().as_str() {
+                "en" => Some(Locale::En),
+                "nl" => Some(Locale::Nl),
+                _ => None,
+            }
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Locale {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let locale = request
+            .headers()
+            .get_one("Accept-Language")
+            .and_then(Locale::negotiate)
+            .unwrap_or(Locale::En);
+        Outcome::Success(locale)
+    }
+}
+
+// Formats an attribute value for display, or None to show it as received
+pub trait Formatter: Send + Sync {
+    fn format(&self, value: &str, locale: Locale) -> Option<String>;
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum AttributeFormat {
+    Date,
+    Phone,
+    Address,
+    // Shown as received, to opt attributes out of the default formats
+    None,
+}
+
+// Dates as YYYY-MM-DD
+struct DateFormatter;
+
+const MONTHS_EN: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const MONTHS_NL: [&str; 12] = [
+    "januari",
+    "februari",
+    "maart",
+    "april",
+    "mei",
+    "juni",
+    "juli",
+    "augustus",
+    "september",
+    "oktober",
+    "november",
+    "december",
+];
+
+impl Formatter for DateFormatter {
+    fn format(&self, value: &str, locale: Locale) -> Option<String> {
+        let mut parts = value.trim().splitn(3, '-');
+        let year: u32 = parts.next()?.parse().ok()?;
+        let month: usize = parts.next()?.parse().ok()?;
+        let day: u32 = parts.next()?.parse().ok()?;
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return None;
+        }
+        Some(match locale {
+            Locale::En => format!("{} {}, {}", MONTHS_EN[month - 1], day, year),
+            Locale::Nl => format!("{} {} {}", day, MONTHS_NL[month - 1], year),
+        })
+    }
+}
+
+// Phone numbers in international format; Dutch numbers are shown in national
+// format to Dutch readers
+struct PhoneFormatter;
+
+impl Formatter for PhoneFormatter {
+    fn format(&self, value: &str, locale: Locale) -> Option<String> {
+        let digits: String = value
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '(' | ')' | '.'))
+            .collect();
+        let number = digits.strip_prefix('+')?;
+        if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        match (locale, number.strip_prefix("31")) {
+            (Locale::Nl, Some(national)) if national.starts_with('6') => {
+                Some(format!("0{} {}", &national[..1], &national[1..]))
+            }
+            (Locale::Nl, Some(national)) => Some(format!("0{}", national)),
+            (Locale::En, Some(national)) => Some(format!("+31 {}", national)),
+            (_, None) => Some(format!("+{}", number)),
+        }
+    }
+}
+
+// Addresses as lines or comma separated components, shown on a single line
+// with Dutch postal codes normalized to "1234 AB"
+struct AddressFormatter;
+
+fn postal_code(component: &str) -> Option<String> {
+    let compact: String = component.chars().filter(|c| *c != ' ').collect();
+    if !compact.is_ascii() {
+        return None;
+    }
+    let (digits, letters) = compact.split_at(compact.len().min(4));
+    if digits.len() == 4
+        && letters.len() == 2
+        && digits.chars().all(|c| c.is_ascii_digit())
+        && letters.chars().all(|c| c.is_ascii_alphabetic())
+    {
+        Some(format!("{} {}", digits, letters.to_ascii_uppercase()))
+    } else {
+        None
+    }
+}
+
+// "1234 AB City" or "1234AB City"
+fn address_component(component: &str) -> String {
+    if let Some(code) = postal_code(component) {
+        return code;
+    }
+    let words: Vec<_> = component.split_whitespace().collect();
+    for n in [2, 1] {
+        if words.len() > n {
+            if let Some(code) = postal_code(&words[..n].concat()) {
+                return format!("{} {}", code, words[n..].join(" "));
+            }
+        }
+    }
+    component.to_string()
+}
+
+impl Formatter for AddressFormatter {
+    fn format(&self, value: &str, _locale: Locale) -> Option<String> {
+        let components: Vec<_> = value
+            .split(|c| c == '\n' || c == ',')
+            .map(str::trim)
+            .filter(|component| !component.is_empty())
+            .map(address_component)
+            .collect();
+        if components.is_empty() {
+            None
+        } else {
+            Some(components.join(", "))
+        }
+    }
+}
+
+impl AttributeFormat {
+    fn formatter(self) -> Option<Box<dyn Formatter>> {
+        match self {
+            AttributeFormat::Date => Some(Box::new(DateFormatter)),
+            AttributeFormat::Phone => Some(Box::new(PhoneFormatter)),
+            AttributeFormat::Address => Some(Box::new(AddressFormatter)),
+            AttributeFormat::None => None,
+        }
+    }
+}
+
+// Attribute name patterns with a single `*` wildcard, matched in order
+fn matches(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        Some((prefix, suffix)) => {
+            name.len() >= prefix.len() + suffix.len()
+                && name.starts_with(prefix)
+                && name.ends_with(suffix)
+        }
+        None => pattern == name,
+    }
+}
+
+const DEFAULT_FORMATS: [(&str, AttributeFormat); 6] = [
+    ("*date_of_birth", AttributeFormat::Date),
+    ("*birthdate", AttributeFormat::Date),
+    ("*_date", AttributeFormat::Date),
+    ("*phone*", AttributeFormat::Phone),
+    ("*address*", AttributeFormat::Address),
+    ("*postal*", AttributeFormat::Address),
+];
+
+pub struct FormatterRegistry {
+    formatters: Vec<(String, Option<Box<dyn Formatter>>)>,
+}
+
+impl FormatterRegistry {
+    // Configured patterns take precedence over the defaults
+    pub fn new(formats: &HashMap<String, AttributeFormat>) -> FormatterRegistry {
+        let mut configured: Vec<_> = formats.iter().collect();
+        // Longer, so more specific, patterns first
+        configured.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()).then(a.cmp(b)));
+        let formatters = configured
+            .into_iter()
+            .map(|(pattern, format)| (pattern.as_str(), *format))
+            .chain(DEFAULT_FORMATS.iter().copied())
+            .map(|(pattern, format)| (pattern.to_string(), format.formatter()))
+            .collect();
+        FormatterRegistry { formatters }
+    }
+
+    pub fn format(&self, name: &str, value: &str, locale: Locale) -> Option<String> {
+        let (_, formatter) = self
+            .formatters
+            .iter()
+            .find(|(pattern, _)| matches(pattern, name))?;
+        formatter
+            .as_ref()?
+            .format(value, locale)
+            .filter(|formatted| formatted != value)
+    }
+}
@@ -0,0 +1,106 @@
+use id_contact_proto::{StartCommRequest, StartCommResponse};
+use rocket::{fairing::AdHoc, Build, Rocket};
+use std::net::SocketAddr;
+use tonic::{transport::Server, Request, Response, Status};
+use tracing::{error, info};
+
+use crate::{reload::LiveConfig, session::SessionStore, Error};
+
+mod proto {
+    tonic::include_proto!("idcontact.comm");
+}
+
+use proto::comm_plugin_server::{CommPlugin, CommPluginServer};
+
+// Conversions to and from the shared JSON types, so that both transports
+// run the exact same request handling
+impl From<proto::StartCommRequest> for StartCommRequest {
+    fn from(request: proto::StartCommRequest) -> StartCommRequest {
+        StartCommRequest {
+            purpose: request.purpose,
+            auth_result: request.auth_result,
+        }
+    }
+}
+
+impl From<StartCommResponse> for proto::StartCommResponse {
+    fn from(response: StartCommResponse) -> proto::StartCommResponse {
+        proto::StartCommResponse {
+            client_url: response.client_url,
+            attr_url: response.attr_url,
+        }
+    }
+}
+
+// Report errors with the gRPC code closest to the HTTP status the same
+// error gets on the HTTP interface
+fn to_status(error: Error) -> Status {
+    let message = error.to_string();
+    match error.status().code {
+        400 => Status::invalid_argument(message),
+        401 => Status::unauthenticated(message),
+        403 => Status::permission_denied(message),
+        404 => Status::not_found(message),
+        409 => Status::already_exists(message),
+        413 => Status::resource_exhausted(message),
+        502 => Status::unavailable(message),
+        504 => Status::deadline_exceeded(message),
+        _ => Status::internal(message),
+    }
+}
+
+struct GrpcPlugin {
+    config: LiveConfig,
+    sessions: SessionStore,
+}
+
+#[tonic::async_trait]
+impl CommPlugin for GrpcPlugin {
+    async fn start_communication(
+        &self,
+        request: Request<proto::StartCommRequest>,
+    ) -> Result<Response<proto::StartCommResponse>, Status> {
+        let request = StartCommRequest::from(request.into_inner());
+        info!("Received gRPC communication request {:?}", request);
+
+        let config = self.config.current();
+        let response =
+            crate::start_communication(&request, &config, &self.sessions).map_err(to_status)?;
+        Ok(Response::new(response.into()))
+    }
+}
+
+async fn serve(
+    address: SocketAddr,
+    config: LiveConfig,
+    sessions: SessionStore,
+) -> Result<(), tonic::transport::Error> {
+    Server::builder()
+        .add_service(CommPluginServer::new(GrpcPlugin { config, sessions }))
+        .serve(address)
+        .await
+}
+
+pub fn attach(base: Rocket<Build>) -> Rocket<Build> {
+    base.attach(AdHoc::on_liftoff("gRPC server", |rocket| {
+        Box::pin(async move {
+            let config = match rocket.state::<LiveConfig>() {
+                Some(config) => config.clone(),
+                None => return,
+            };
+            let sessions = match rocket.state::<SessionStore>() {
+                Some(sessions) => sessions.clone(),
+                None => return,
+            };
+
+            if let Some(address) = config.current().grpc_address() {
+                info!("Starting gRPC server on {}", address);
+                rocket::tokio::spawn(async move {
+                    if let Err(e) = serve(address, config, sessions).await {
+                        error!("gRPC server failed: {}", e);
+                    }
+                });
+            }
+        })
+    }))
+}
@@ -0,0 +1,146 @@
+use std::{convert::Infallible, sync::Mutex};
+
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    request::{FromRequest, Outcome},
+    Request, Response,
+};
+use serde::Deserialize;
+
+use crate::{
+    clock::Clock,
+    session::{Session, SessionStore},
+};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct HeaderRule {
+    // Exact request path to apply the header to, or "*" for all routes
+    route: String,
+    name: String,
+    // May contain {method}, {path}, {query}, {header:<name>} and
+    // {session:id}, {session:purpose} and {session:status} placeholders
+    value: String,
+}
+
+#[derive(Default)]
+struct SessionSlot(Mutex<Option<String>>);
+
+// Lets handlers name the session a request created, for the session
+// placeholders. Other requests are matched to a session by their session or
+// handle query parameter.
+pub struct HandledSession<'r>(&'r SessionSlot);
+
+impl HandledSession<'_> {
+    pub fn set(&self, id: &str) {
+        *self.0 .0.lock().unwrap() = Some(id.to_string());
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for HandledSession<'r> {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(HandledSession(request.local_cache(SessionSlot::default)))
+    }
+}
+
+fn session(request: &Request<'_>) -> Option<Session> {
+    let sessions = request.rocket().state::<SessionStore>()?;
+    let handled = request
+        .local_cache(SessionSlot::default)
+        .0
+        .lock()
+        .unwrap()
+        .clone();
+    let id = match handled {
+        Some(id) => id,
+        None => match request.query_value::<&str>("session").and_then(Result::ok) {
+            Some(id) => id.to_string(),
+            None => {
+                let handle = request.query_value::<&str>("handle")?.ok()?;
+                let clock = crate::reload::current(request)
+                    .map(|config| Clock::with_skew(config.clock_skew()))
+                    .unwrap_or_default();
+                sessions.resolve_handle(handle, &clock)?
+            }
+        },
+    };
+    sessions.get(&id)
+}
+
+// Applies the response_headers of the configuration the request was
+// handled with, so rules follow reloads
+pub struct HeaderInjector;
+
+fn render(template: &str, request: &Request<'_>, session: Option<&Session>) -> String {
+    let mut result = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+        result.push_str(&rest[..start]);
+
+        let variable = &rest[start + 1..end];
+        match variable {
+            "method" => result.push_str(request.method().as_str()),
+            "path" => result.push_str(request.uri().path().as_str()),
+            "query" => {
+                if let Some(query) = request.uri().query() {
+                    result.push_str(query.as_str());
+                }
+            }
+            "session:id" => result.push_str(session.map_or("", Session::id)),
+            "session:purpose" => result.push_str(session.map_or("", Session::purpose)),
+            "session:status" => result.push_str(session.map_or("", Session::status)),
+            _ => {
+                if let Some(header) = variable.strip_prefix("header:") {
+                    result.push_str(request.headers().get_one(header).unwrap_or(""));
+                } else {
+                    // Leave unknown placeholders untouched
+                    result.push_str(&rest[start..=end]);
+                }
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+#[rocket::async_trait]
+impl Fairing for HeaderInjector {
+    fn info(&self) -> Info {
+        Info {
+            name: "Response header injection",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let config = match crate::reload::current(request) {
+            Some(config) => config,
+            None => return,
+        };
+        let path = request.uri().path();
+        let rules: Vec<_> = config
+            .response_headers()
+            .iter()
+            .filter(|rule| rule.route == "*" || rule.route == path.as_str())
+            .collect();
+        // Only looked up when needed, as that takes a lock on the store
+        let session = if rules.iter().any(|rule| rule.value.contains("{session:")) {
+            session(request)
+        } else {
+            None
+        };
+        for rule in rules {
+            response.set_raw_header(
+                rule.name.clone(),
+                render(&rule.value, request, session.as_ref()),
+            );
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+
+use josekit::{
+    jwe::{JweEncrypter, JweHeader, ECDH_ES, RSA_OAEP},
+    jwk::Jwk,
+    JoseError,
+};
+use rocket::{fairing::AdHoc, get, http::Status, serde::json::Json, Build, Rocket, State};
+use serde::Serialize;
+use tracing::{error, info};
+
+use crate::{
+    config::Config,
+    drain::{Drain, Remaining},
+    keys::PublicJwk,
+    reload::LiveConfig,
+};
+
+const PROBE: &[u8] = b"readiness probe";
+
+#[derive(Serialize)]
+pub struct Check {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+impl Check {
+    fn ok() -> Check {
+        Check {
+            ok: true,
+            detail: None,
+        }
+    }
+
+    fn failed(detail: impl ToString) -> Check {
+        Check {
+            ok: false,
+            detail: Some(detail.to_string()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Readiness {
+    ready: bool,
+    checks: BTreeMap<String, Check>,
+}
+
+fn encrypter(public_key: &PublicJwk) -> Result<Box<dyn JweEncrypter>, JoseError> {
+    let jwk = Jwk::from_map(public_key.clone())?;
+    Ok(match jwk.algorithm() {
+        Some("ECDH-ES") => Box::new(ECDH_ES.encrypter_from_jwk(&jwk)?),
+        _ => Box::new(RSA_OAEP.encrypter_from_jwk(&jwk)?),
+    })
+}
+
+// Encrypts to the published public key and decrypts with the configured
+// private key, which only succeeds if the two belong together
+fn check_decryption(config: &Config, public_key: &PublicJwk) -> Check {
+    let kid = public_key["kid"].as_str().unwrap_or_default();
+    let round_trip = || -> Result<bool, JoseError> {
+        let mut header = JweHeader::new();
+        header.set_content_encryption("A256GCM");
+        header.set_key_id(kid);
+        let token = josekit::jwe::serialize_compact(PROBE, &header, &*encrypter(public_key)?)?;
+        let decrypter = config.decrypters(Some(kid))[0];
+        let (payload, _) = josekit::jwe::deserialize_compact(&token, decrypter)?;
+        Ok(payload == PROBE)
+    };
+
+    match round_trip() {
+        Ok(true) => Check::ok(),
+        Ok(false) => Check::failed("Decrypted probe does not match"),
+        Err(e) => Check::failed(e),
+    }
+}
+
+// Round trips a probe through every decryption key, as done with --warmup
+// so the first real request doesn't pay for cold crypto code paths
+pub fn warm_up(config: &Config) -> Result<(), String> {
+    for public_key in config
+        .public_keys()
+        .iter()
+        .filter(|key| key["use"] == "enc")
+    {
+        let check = check_decryption(config, public_key);
+        if !check.ok {
+            return Err(check.detail.unwrap_or_default());
+        }
+    }
+    Ok(())
+}
+
+fn check_validation(config: &Config) -> Check {
+    match config.remote_validator() {
+        Some(remote) if remote.key_count() == 0 => {
+            Check::failed("No verification keys fetched from JWKS yet")
+        }
+        // Static keys were already parsed when loading the configuration
+        _ => Check::ok(),
+    }
+}
+
+#[derive(Serialize)]
+pub struct Health {
+    status: &'static str,
+    #[serde(flatten)]
+    remaining: Remaining,
+}
+
+// Reports what is left to finish during graceful shutdown, so harnesses can
+// tell whether draining completes
+#[get("/health")]
+pub fn health(drain: &State<Drain>) -> Json<Health> {
+    let mut remaining = drain.remaining();
+    // Not counting the request asking for this
+    remaining.in_flight_requests = remaining.in_flight_requests.saturating_sub(1);
+    Json(Health {
+        status: if drain.draining() { "draining" } else { "ok" },
+        remaining,
+    })
+}
+
+#[get("/health/live")]
+pub fn live() -> &'static str {
+    "ok"
+}
+
+#[get("/health/ready")]
+pub fn ready(config: &Config, drain: &State<Drain>) -> (Status, Json<Readiness>) {
+    let mut checks = BTreeMap::new();
+    checks.insert(
+        "accepting_traffic".to_string(),
+        if drain.draining() {
+            Check::failed("Shutting down")
+        } else {
+            Check::ok()
+        },
+    );
+    for public_key in config
+        .public_keys()
+        .iter()
+        .filter(|key| key["use"] == "enc")
+    {
+        let kid = public_key["kid"].as_str().unwrap_or_default();
+        checks.insert(
+            format!("decryption:{}", kid),
+            check_decryption(config, public_key),
+        );
+    }
+    checks.insert("signature_validation".to_string(), check_validation(config));
+
+    let ready = checks.values().all(|check| check.ok);
+    let status = if ready {
+        Status::Ok
+    } else {
+        Status::ServiceUnavailable
+    };
+    (status, Json(Readiness { ready, checks }))
+}
+
+async fn check_internal_url(url: String) {
+    match reqwest::get(&url).await.and_then(|r| r.error_for_status()) {
+        Ok(_) => info!("internal_url is reachable"),
+        Err(e) => error!(
+            "internal_url does not reach this plugin, brokers won't be able to either: {}",
+            e
+        ),
+    }
+}
+
+// Optionally verifies internal_url actually leads back here, once the
+// server is listening
+pub fn attach(base: Rocket<Build>) -> Rocket<Build> {
+    base.attach(AdHoc::on_liftoff("Internal url check", |rocket| {
+        Box::pin(async move {
+            let config = match rocket.state::<LiveConfig>() {
+                Some(live) => live.current(),
+                None => return,
+            };
+            if config.check_internal_url() {
+                let url = format!("{}/health/live", config.internal_url());
+                // Spawned, as the server only starts serving after liftoff
+                rocket::tokio::spawn(check_internal_url(url));
+            }
+        })
+    }))
+}
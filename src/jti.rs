@@ -0,0 +1,101 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use tracing::info;
+
+use crate::{config::Config, keys, saml::Assertion, Error};
+
+fn default_window() -> u64 {
+    3600
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ReplayProtectionConfig {
+    // Seconds an auth result is remembered
+    #[serde(default = "default_window")]
+    window: u64,
+}
+
+// Remembers submitted auth results, so the same one can't be delivered twice.
+// The window is taken from the configuration of each request.
+#[derive(Default)]
+pub struct JtiCache {
+    seen: Mutex<HashMap<String, Instant>>,
+    rejected: AtomicU64,
+}
+
+fn digest(text: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+// Identifies an auth result by its jti claim, or without one by the token
+fn token_id(token: &str, config: &Config) -> String {
+    let kid = keys::jwe_key_id(token);
+    let jti = config
+        .decrypters(kid.as_deref())
+        .into_iter()
+        .find_map(|decrypter| keys::unverified_claims(token, decrypter))
+        .and_then(|claims| claims["jti"].as_str().map(|jti| jti.to_string()));
+    match jti {
+        Some(jti) => format!("jti:{}", jti),
+        None => format!("token:{}", digest(token)),
+    }
+}
+
+impl JtiCache {
+    // Records the auth result, rejecting it when it was submitted before.
+    // Only call this for auth results that decoded successfully.
+    pub fn check(&self, token: &str, config: &Config) -> Result<(), Error> {
+        self.remember(|| token_id(token, config), config)
+    }
+
+    // As check, for SAML responses, identified by the ID of their assertion
+    pub fn check_saml(
+        &self,
+        assertion: &Assertion,
+        response: &str,
+        config: &Config,
+    ) -> Result<(), Error> {
+        self.remember(
+            || match &assertion.id {
+                Some(id) => format!("saml:{}", id),
+                None => format!("saml-response:{}", digest(response)),
+            },
+            config,
+        )
+    }
+
+    fn remember(&self, id: impl FnOnce() -> String, config: &Config) -> Result<(), Error> {
+        let window = match config.replay_protection() {
+            Some(replay_protection) => Duration::from_secs(replay_protection.window),
+            None => return Ok(()),
+        };
+
+        let id = id();
+        let now = Instant::now();
+        let mut seen = self.seen.lock().unwrap();
+        seen.retain(|_, at| now.duration_since(*at) <= window);
+        if seen.contains_key(&id) {
+            info!("Auth result {} submitted again", id);
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(Error::ReplayedAuthResult);
+        }
+        seen.insert(id, now);
+        Ok(())
+    }
+
+    // Number of resubmitted auth results rejected since startup
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+}
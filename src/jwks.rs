@@ -0,0 +1,195 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock, Weak},
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use josekit::{
+    jwk::JwkSet,
+    jws::{JwsAlgorithm, JwsVerifier, ES256, ES384, RS256},
+    JoseError,
+};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::keys;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JwksAlgorithm {
+    RS256,
+    ES256,
+    ES384,
+}
+
+impl Default for JwksAlgorithm {
+    fn default() -> JwksAlgorithm {
+        JwksAlgorithm::RS256
+    }
+}
+
+impl JwksAlgorithm {
+    fn verifier(&self, jwk: &josekit::jwk::Jwk) -> Result<Box<dyn JwsVerifier>, JoseError> {
+        Ok(match self {
+            JwksAlgorithm::RS256 => Box::new(RS256.verifier_from_jwk(jwk)?),
+            JwksAlgorithm::ES256 => Box::new(ES256.verifier_from_jwk(jwk)?),
+            JwksAlgorithm::ES384 => Box::new(ES384.verifier_from_jwk(jwk)?),
+        })
+    }
+}
+
+pub fn default_refresh_interval() -> u64 {
+    300
+}
+
+type KeySet = Vec<(Option<String>, Box<dyn JwsVerifier>)>;
+
+// Key sets per url and algorithm, shared by every verifier for them so that
+// configuration reloads don't each start another refresher
+#[allow(clippy::type_complexity)]
+static SHARED: Mutex<Option<HashMap<(String, JwksAlgorithm), Weak<RwLock<KeySet>>>>> =
+    Mutex::new(None);
+
+// Verifier backed by a remote JWKS, refreshed in the background for as
+// long as a verifier for the same url is alive.
+//
+// The JWS header never reaches the verifier through id_contact_jwt, so the
+// kid is read back from the signing input. Without one, every key usable
+// with the configured algorithm is tried.
+#[derive(Debug, Clone)]
+pub struct JwksVerifier {
+    algorithm: JwksAlgorithm,
+    keys: Arc<RwLock<KeySet>>,
+}
+
+fn parse(body: &[u8], algorithm: JwksAlgorithm) -> Result<KeySet, JoseError> {
+    let set = JwkSet::from_bytes(body)?;
+    Ok(set
+        .keys()
+        .into_iter()
+        // Skip keys meant for other algorithms or for encryption
+        .filter_map(|jwk| {
+            let verifier = algorithm.verifier(jwk).ok()?;
+            Some((jwk.key_id().map(|kid| kid.to_string()), verifier))
+        })
+        .collect())
+}
+
+async fn fetch(
+    url: &str,
+    algorithm: JwksAlgorithm,
+) -> Result<KeySet, Box<dyn std::error::Error + Send + Sync>> {
+    let body = reqwest::get(url).await?.error_for_status()?.bytes().await?;
+    Ok(parse(&body, algorithm)?)
+}
+
+// The blocking client can't be used on a runtime thread, so the first fetch
+// at startup runs on a thread of its own
+fn fetch_blocking(url: &str, algorithm: JwksAlgorithm) -> Result<KeySet, String> {
+    let url = url.to_string();
+    std::thread::spawn(move || {
+        let body = reqwest::blocking::get(&url)
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.bytes())
+            .map_err(|e| e.to_string())?;
+        parse(&body, algorithm).map_err(|e| e.to_string())
+    })
+    .join()
+    .unwrap_or_else(|_| Err("Fetching panicked".to_string()))
+}
+
+fn store(url: &str, keys: &RwLock<KeySet>, new_keys: KeySet) {
+    let kids: Vec<_> = new_keys.iter().filter_map(|(kid, _)| kid.clone()).collect();
+    info!("Fetched verification keys {:?} from {}", kids, url);
+    *keys.write().unwrap() = new_keys;
+}
+
+async fn refresh(
+    url: String,
+    interval: Duration,
+    algorithm: JwksAlgorithm,
+    keys: Weak<RwLock<KeySet>>,
+) {
+    loop {
+        rocket::tokio::time::sleep(interval).await;
+        let result = fetch(&url, algorithm).await;
+        let keys = match keys.upgrade() {
+            Some(keys) => keys,
+            None => return,
+        };
+        match result {
+            Ok(new_keys) => store(&url, &keys, new_keys),
+            // Keep the previous keys, a temporary outage shouldn't stop verification
+            Err(e) => warn!("Failed to fetch JWKS from {}: {}", url, e),
+        }
+    }
+}
+
+impl JwksVerifier {
+    pub fn new(url: String, algorithm: JwksAlgorithm, refresh_interval: u64) -> JwksVerifier {
+        let mut shared = SHARED.lock().unwrap();
+        let shared = shared.get_or_insert_with(HashMap::new);
+        shared.retain(|_, keys| keys.strong_count() > 0);
+        if let Some(keys) = shared
+            .get(&(url.clone(), algorithm))
+            .and_then(|keys| keys.upgrade())
+        {
+            return JwksVerifier { algorithm, keys };
+        }
+
+        let keys = Arc::new(RwLock::new(Vec::new()));
+        match fetch_blocking(&url, algorithm) {
+            Ok(new_keys) => store(&url, &keys, new_keys),
+            // Readiness reports the empty key set until a refresh succeeds
+            Err(e) => warn!("Failed to fetch JWKS from {}: {}", url, e),
+        }
+        let weak = Arc::downgrade(&keys);
+        shared.insert((url.clone(), algorithm), weak.clone());
+        let interval = Duration::from_secs(refresh_interval.max(1));
+        match rocket::tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(refresh(url, interval, algorithm, weak));
+            }
+            Err(_) => warn!("Not refreshing JWKS from {} outside of a runtime", url),
+        }
+        JwksVerifier { algorithm, keys }
+    }
+
+    pub fn key_count(&self) -> usize {
+        self.keys.read().unwrap().len()
+    }
+}
+
+impl JwsVerifier for JwksVerifier {
+    fn algorithm(&self) -> &dyn JwsAlgorithm {
+        match self.algorithm {
+            JwksAlgorithm::RS256 => &RS256,
+            JwksAlgorithm::ES256 => &ES256,
+            JwksAlgorithm::ES384 => &ES384,
+        }
+    }
+
+    fn key_id(&self) -> Option<&str> {
+        None
+    }
+
+    fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), JoseError> {
+        let kid = std::str::from_utf8(message).ok().and_then(keys::jwe_key_id);
+        let keys = self.keys.read().unwrap();
+        if keys
+            .iter()
+            .filter(|(key_id, _)| kid.is_none() || *key_id == kid)
+            .any(|(_, verifier)| verifier.verify(message, signature).is_ok())
+        {
+            Ok(())
+        } else {
+            Err(JoseError::InvalidSignature(anyhow!(
+                "No key in the JWKS matches the signature"
+            )))
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn JwsVerifier> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use rdkafka::{
+    producer::{FutureProducer, FutureRecord},
+    ClientConfig,
+};
+use rocket::{fairing::AdHoc, tokio::sync::broadcast::error::RecvError, Build, Rocket};
+use serde::Deserialize;
+use tracing::{error, info, warn};
+
+use crate::{
+    events::{Event, Events},
+    reload::LiveConfig,
+};
+
+const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct KafkaConfig {
+    // Comma separated host:port pairs
+    pub brokers: String,
+    pub topic: String,
+}
+
+// Name of the lifecycle stage an event marks, if any. Auth results not
+// linked to a session aren't part of a session's lifecycle.
+fn lifecycle(event: &Event) -> Option<&'static str> {
+    match event.kind() {
+        "session_started" => Some("started"),
+        "auth_result_received" if !event.data()["session_id"].is_null() => {
+            Some("attributes_received")
+        }
+        "session_completed" => Some("completed"),
+        _ => None,
+    }
+}
+
+async fn produce(config: KafkaConfig, events: Events) {
+    let producer: FutureProducer = match ClientConfig::new()
+        .set("bootstrap.servers", &config.brokers)
+        .create()
+    {
+        Ok(producer) => producer,
+        Err(e) => {
+            error!("Failed to create Kafka producer: {}", e);
+            return;
+        }
+    };
+
+    info!(
+        "Publishing session lifecycle events to Kafka topic {}",
+        config.topic
+    );
+    let mut receiver = events.subscribe();
+    loop {
+        let event = match receiver.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Kafka producer fell behind, skipped {} events", skipped);
+                events.record_dropped(skipped);
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        let stage = match lifecycle(&event) {
+            Some(stage) => stage,
+            None => continue,
+        };
+
+        let mut payload = serde_json::to_value(&event).unwrap_or_default();
+        payload["type"] = stage.into();
+        let payload = payload.to_string();
+        // Keyed by session, so a session's events stay in order
+        let key = event.data()["session_id"].as_str().unwrap_or_default();
+        let record = FutureRecord::to(&config.topic).key(key).payload(&payload);
+        if let Err((e, _)) = producer.send(record, SEND_TIMEOUT).await {
+            warn!("Failed to publish {} event to Kafka: {}", stage, e);
+        }
+    }
+}
+
+pub fn attach(base: Rocket<Build>) -> Rocket<Build> {
+    base.attach(AdHoc::on_liftoff("Kafka producer", |rocket| {
+        Box::pin(async move {
+            let config = match rocket
+                .state::<LiveConfig>()
+                .and_then(|live| live.current().kafka().cloned())
+            {
+                Some(config) => config,
+                None => return,
+            };
+            let events = match rocket.state::<Events>() {
+                Some(events) => events.clone(),
+                None => return,
+            };
+            rocket::tokio::spawn(produce(config, events));
+        })
+    }))
+}
@@ -0,0 +1,146 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rocket::{get, serde::json::Json};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{access::Viewer, config::Config};
+
+const DAY: u64 = 24 * 60 * 60;
+
+fn default_warn_days() -> u64 {
+    14
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct KeyRotationConfig {
+    // Days without use after which a key is reported as unused
+    unused_days: Option<u64>,
+    // Unix time by which keys, by kid or issuer, are to be rotated
+    #[serde(default)]
+    deadlines: HashMap<String, u64>,
+    // Days before a deadline to start warning
+    #[serde(default = "default_warn_days")]
+    warn_days: u64,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyKind {
+    Decryption,
+    Verification,
+}
+
+#[derive(Default, Clone, Copy)]
+struct Counts {
+    uses: u64,
+    failures: u64,
+    last_used: Option<u64>,
+}
+
+static USAGE: Mutex<BTreeMap<(KeyKind, String), Counts>> = Mutex::new(BTreeMap::new());
+static STARTED: AtomicU64 = AtomicU64::new(0);
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+// Marks the start of the period keys are expected to be used in
+pub fn init() {
+    STARTED.store(unix_now(), Ordering::Relaxed);
+}
+
+pub fn record(kind: KeyKind, id: &str, success: bool) {
+    let mut usage = USAGE.lock().unwrap();
+    let counts = usage.entry((kind, id.to_string())).or_default();
+    if success {
+        counts.uses += 1;
+    } else {
+        counts.failures += 1;
+    }
+    counts.last_used = Some(unix_now());
+}
+
+#[derive(Serialize)]
+pub struct KeyReport {
+    kind: KeyKind,
+    id: String,
+    uses: u64,
+    failures: u64,
+    last_used: Option<u64>,
+    rotation_deadline: Option<u64>,
+    warnings: Vec<String>,
+}
+
+fn warnings(config: Option<&KeyRotationConfig>, id: &str, counts: &Counts) -> Vec<String> {
+    let config = match config {
+        Some(config) => config,
+        None => return Vec::new(),
+    };
+    let now = unix_now();
+    let mut warnings = Vec::new();
+
+    if let Some(unused_days) = config.unused_days {
+        let since = counts
+            .last_used
+            .unwrap_or_else(|| STARTED.load(Ordering::Relaxed));
+        if now.saturating_sub(since) > unused_days.saturating_mul(DAY) {
+            warnings.push(format!("Not used in the last {} days", unused_days));
+        }
+    }
+    if let Some(&deadline) = config.deadlines.get(id) {
+        if deadline <= now {
+            warnings.push("Rotation deadline passed".to_string());
+        } else if deadline - now <= config.warn_days.saturating_mul(DAY) {
+            warnings.push(format!(
+                "Rotation deadline in {} days",
+                (deadline - now) / DAY
+            ));
+        }
+    }
+    warnings
+}
+
+// Usage of every configured key, also those never used
+pub fn report(config: &Config) -> Vec<KeyReport> {
+    let usage = USAGE.lock().unwrap();
+    let rotation = config.key_rotation();
+    config
+        .key_ids()
+        .into_iter()
+        .map(|(kind, id)| {
+            let counts = usage.get(&(kind, id.clone())).copied().unwrap_or_default();
+            KeyReport {
+                kind,
+                warnings: warnings(rotation, &id, &counts),
+                rotation_deadline: rotation
+                    .and_then(|rotation| rotation.deadlines.get(&id).copied()),
+                id,
+                uses: counts.uses,
+                failures: counts.failures,
+                last_used: counts.last_used,
+            }
+        })
+        .collect()
+}
+
+#[get("/admin/keys")]
+pub fn list(_viewer: Viewer, config: &Config) -> Json<Vec<KeyReport>> {
+    let report = report(config);
+    for key in &report {
+        for warning in &key.warnings {
+            warn!("Key {}: {}", key.id, warning);
+        }
+    }
+    Json(report)
+}
@@ -0,0 +1,252 @@
+use std::convert::TryFrom;
+
+use anyhow::anyhow;
+use josekit::{
+    jwe::{JweDecrypter, JweEncrypter, ECDH_ES, RSA_OAEP},
+    jwk::{
+        alg::{ec::EcKeyPair, rsa::RsaKeyPair},
+        Jwk,
+    },
+    jws::{JwsSigner, JwsVerifier, ES256, ES384, RS256},
+    JoseError,
+};
+use rocket::get;
+use serde::{de::Error as _, Deserialize, Deserializer};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    caching::Cached,
+    config::Config,
+    jwks::{self, JwksAlgorithm, JwksVerifier},
+};
+
+// Local counterparts of the id_contact_jwt key configs, which only know
+// about RSA keys. Keys are PEM encoded, as before, or base64 encoded PEM
+// for places where newlines are awkward, such as environment variables.
+
+fn pem<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let key = String::deserialize(deserializer)?;
+    if key.trim_start().starts_with("-----BEGIN") {
+        return Ok(key);
+    }
+    base64::decode(key.trim())
+        .ok()
+        .and_then(|key| String::from_utf8(key).ok())
+        .ok_or_else(|| D::Error::custom("key is neither PEM nor base64 encoded PEM"))
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum EcCurve {
+    #[serde(rename = "P-256")]
+    P256,
+    #[serde(rename = "P-384")]
+    P384,
+}
+
+impl Default for EcCurve {
+    fn default() -> EcCurve {
+        EcCurve::P256
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum EncryptionKeyConfig {
+    #[serde(rename = "RSA")]
+    Rsa {
+        #[serde(deserialize_with = "pem")]
+        key: String,
+    },
+    // ECDH-ES takes the curve from the key itself
+    #[serde(rename = "EC")]
+    Ec {
+        #[serde(deserialize_with = "pem")]
+        key: String,
+    },
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum SignKeyConfig {
+    #[serde(rename = "RSA")]
+    Rsa {
+        #[serde(deserialize_with = "pem")]
+        key: String,
+    },
+    #[serde(rename = "EC")]
+    Ec {
+        #[serde(deserialize_with = "pem")]
+        key: String,
+        #[serde(default)]
+        curve: EcCurve,
+    },
+    // Only usable for verification
+    #[serde(rename = "jwks")]
+    Jwks {
+        url: String,
+        #[serde(default)]
+        algorithm: JwksAlgorithm,
+        // Seconds between refreshes of the key set
+        #[serde(default = "jwks::default_refresh_interval")]
+        refresh_interval: u64,
+    },
+}
+
+fn jwks_not_supported() -> JoseError {
+    JoseError::InvalidKeyFormat(anyhow!("A jwks key can only be used for verification"))
+}
+
+impl TryFrom<EncryptionKeyConfig> for Box<dyn JweDecrypter> {
+    type Error = JoseError;
+    fn try_from(config: EncryptionKeyConfig) -> Result<Self, JoseError> {
+        Ok(match config {
+            EncryptionKeyConfig::Rsa { key } => Box::new(RSA_OAEP.decrypter_from_pem(key)?),
+            EncryptionKeyConfig::Ec { key } => Box::new(ECDH_ES.decrypter_from_pem(key)?),
+        })
+    }
+}
+
+impl TryFrom<EncryptionKeyConfig> for Box<dyn JweEncrypter> {
+    type Error = JoseError;
+    fn try_from(config: EncryptionKeyConfig) -> Result<Self, JoseError> {
+        Ok(match config {
+            EncryptionKeyConfig::Rsa { key } => Box::new(RSA_OAEP.encrypter_from_pem(key)?),
+            EncryptionKeyConfig::Ec { key } => Box::new(ECDH_ES.encrypter_from_pem(key)?),
+        })
+    }
+}
+
+impl TryFrom<SignKeyConfig> for Box<dyn JwsVerifier> {
+    type Error = JoseError;
+    fn try_from(config: SignKeyConfig) -> Result<Self, JoseError> {
+        Ok(match config {
+            SignKeyConfig::Rsa { key } => Box::new(RS256.verifier_from_pem(key)?),
+            SignKeyConfig::Ec {
+                key,
+                curve: EcCurve::P256,
+            } => Box::new(ES256.verifier_from_pem(key)?),
+            SignKeyConfig::Ec {
+                key,
+                curve: EcCurve::P384,
+            } => Box::new(ES384.verifier_from_pem(key)?),
+            SignKeyConfig::Jwks {
+                url,
+                algorithm,
+                refresh_interval,
+            } => Box::new(JwksVerifier::new(url, algorithm, refresh_interval)),
+        })
+    }
+}
+
+impl TryFrom<SignKeyConfig> for Box<dyn JwsSigner> {
+    type Error = JoseError;
+    fn try_from(config: SignKeyConfig) -> Result<Self, JoseError> {
+        Ok(match config {
+            SignKeyConfig::Rsa { key } => Box::new(RS256.signer_from_pem(key)?),
+            SignKeyConfig::Ec {
+                key,
+                curve: EcCurve::P256,
+            } => Box::new(ES256.signer_from_pem(key)?),
+            SignKeyConfig::Ec {
+                key,
+                curve: EcCurve::P384,
+            } => Box::new(ES384.signer_from_pem(key)?),
+            SignKeyConfig::Jwks { .. } => return Err(jwks_not_supported()),
+        })
+    }
+}
+
+pub type PublicJwk = Map<String, Value>;
+
+fn finish_jwk(mut jwk: Jwk, der: &[u8], key_use: &str, algorithm: &str) -> PublicJwk {
+    // Stable across restarts, so consumers can cache by kid
+    jwk.set_key_id(hex::encode(&Sha256::digest(der)[..8]));
+    jwk.set_key_use(key_use);
+    jwk.set_algorithm(algorithm);
+    jwk.as_ref().clone()
+}
+
+// Public part of one of our own private keys, for publication
+pub fn public_encryption_jwk(config: &EncryptionKeyConfig) -> Result<PublicJwk, JoseError> {
+    Ok(match config {
+        EncryptionKeyConfig::Rsa { key } => {
+            let pair = RsaKeyPair::from_pem(key)?;
+            finish_jwk(
+                pair.to_jwk_public_key(),
+                &pair.to_der_public_key(),
+                "enc",
+                "RSA-OAEP",
+            )
+        }
+        EncryptionKeyConfig::Ec { key } => {
+            let pair = EcKeyPair::from_pem(key, None)?;
+            finish_jwk(
+                pair.to_jwk_public_key(),
+                &pair.to_der_public_key(),
+                "enc",
+                "ECDH-ES",
+            )
+        }
+    })
+}
+
+pub fn public_signing_jwk(config: &SignKeyConfig) -> Result<PublicJwk, JoseError> {
+    Ok(match config {
+        SignKeyConfig::Rsa { key } => {
+            let pair = RsaKeyPair::from_pem(key)?;
+            finish_jwk(
+                pair.to_jwk_public_key(),
+                &pair.to_der_public_key(),
+                "sig",
+                "RS256",
+            )
+        }
+        SignKeyConfig::Ec { key, curve } => {
+            let pair = EcKeyPair::from_pem(key, None)?;
+            let algorithm = match curve {
+                EcCurve::P256 => "ES256",
+                EcCurve::P384 => "ES384",
+            };
+            finish_jwk(
+                pair.to_jwk_public_key(),
+                &pair.to_der_public_key(),
+                "sig",
+                algorithm,
+            )
+        }
+        SignKeyConfig::Jwks { .. } => return Err(jwks_not_supported()),
+    })
+}
+
+// The kid from the protected header of a compact JWE or JWS, if any
+pub fn jwe_key_id(token: &str) -> Option<String> {
+    let header = token.split('.').next()?;
+    let header = base64::decode_config(header, base64::URL_SAFE_NO_PAD).ok()?;
+    let header: Value = serde_json::from_slice(&header).ok()?;
+    header["kid"].as_str().map(|kid| kid.to_string())
+}
+
+// The claims of a sign-then-encrypt token, read without verifying the
+// signature
+pub fn unverified_claims(token: &str, decrypter: &dyn JweDecrypter) -> Option<Value> {
+    let (jws, _) = josekit::jwe::deserialize_compact(token, decrypter).ok()?;
+    let jws = std::str::from_utf8(&jws).ok()?;
+    let claims = jws.split('.').nth(1)?;
+    let claims = base64::decode_config(claims, base64::URL_SAFE_NO_PAD).ok()?;
+    serde_json::from_slice(&claims).ok()
+}
+
+// The iss claim, for selecting the key to verify the token with
+pub fn unverified_issuer(token: &str, decrypter: &dyn JweDecrypter) -> Option<String> {
+    let claims = unverified_claims(token, decrypter)?;
+    claims["iss"].as_str().map(|iss| iss.to_string())
+}
+
+#[get("/.well-known/jwks.json")]
+pub fn jwks(config: &Config) -> Cached {
+    Cached::json(
+        &json!({ "keys": config.public_keys() }),
+        config.cache_max_age(),
+    )
+}
@@ -0,0 +1,167 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use id_contact_proto::AuthResult;
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    figment::Figment,
+    route::{self, Handler},
+    Data, Request, Response, Route,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::{info, info_span, Instrument, Span};
+use tracing_subscriber::EnvFilter;
+
+use crate::auth_results::REQUEST_ID_HEADER;
+
+// Set once at startup, read wherever attributes might end up in the log
+static LOG_ATTRIBUTES: AtomicBool = AtomicBool::new(false);
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl Default for LogFormat {
+    fn default() -> LogFormat {
+        LogFormat::Text
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct LoggingConfig {
+    // A tracing filter directive, such as "info" or "id_contact_comm_test=debug"
+    #[serde(default = "default_log_level")]
+    log_level: String,
+    #[serde(default)]
+    log_format: LogFormat,
+    #[serde(default)]
+    log_attributes: bool,
+}
+
+// Read straight from the figment rather than from Config, so logging also
+// works when the rest of the configuration fails to load
+pub fn init(figment: &Figment) {
+    let config = figment
+        .extract::<LoggingConfig>()
+        .unwrap_or_else(|_| LoggingConfig {
+            log_level: default_log_level(),
+            log_format: LogFormat::default(),
+            log_attributes: false,
+        });
+    LOG_ATTRIBUTES.store(config.log_attributes, Ordering::Relaxed);
+
+    let filter = EnvFilter::try_new(&config.log_level).unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    // Fails when already initialized, as happens with the demo relaunching
+    let _ = match config.log_format {
+        LogFormat::Json => builder.json().try_init(),
+        LogFormat::Text => builder.try_init(),
+    };
+}
+
+pub fn redact(attributes: &mut Value) {
+    if let Value::Object(attributes) = attributes {
+        for attribute in attributes.values_mut() {
+            *attribute = json!("[redacted]");
+        }
+    }
+}
+
+// Displays an auth result with attribute values hidden, unless
+// log_attributes is set
+pub struct Redacted<'a>(pub &'a AuthResult);
+
+impl<'a> Display for Redacted<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut value = serde_json::to_value(self.0).unwrap_or(Value::Null);
+        if !LOG_ATTRIBUTES.load(Ordering::Relaxed) {
+            if let Some(attributes) = value.get_mut("attributes") {
+                redact(attributes);
+            }
+        }
+        value.fmt(f)
+    }
+}
+
+pub struct RedactedAttributes<'a>(pub &'a HashMap<String, String>);
+
+impl<'a> Display for RedactedAttributes<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut value = serde_json::to_value(self.0).unwrap_or(Value::Null);
+        if !LOG_ATTRIBUTES.load(Ordering::Relaxed) {
+            redact(&mut value);
+        }
+        value.fmt(f)
+    }
+}
+
+struct RequestSpan(Span);
+
+fn request_span<'r>(request: &'r Request<'_>) -> &'r Span {
+    &request.local_cache(|| RequestSpan(Span::none())).0
+}
+
+// Opens a span per request, carrying the method, path and request id. Route
+// handlers only run in it when mounted through instrument below.
+pub struct RequestSpans;
+
+#[rocket::async_trait]
+impl Fairing for RequestSpans {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request spans",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _data: &mut Data<'_>) {
+        let span = info_span!(
+            "request",
+            method = %request.method(),
+            // Only the path, query strings may hold auth results
+            uri = %request.uri().path(),
+            request_id = request.headers().get_one(REQUEST_ID_HEADER).unwrap_or("-"),
+        );
+        span.in_scope(|| info!("Request received"));
+        request.local_cache(|| RequestSpan(span));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let span = request_span(request);
+        span.in_scope(|| info!(status = response.status().code, "Request handled"));
+    }
+}
+
+#[derive(Clone)]
+struct SpannedHandler(Box<dyn Handler>);
+
+#[rocket::async_trait]
+impl Handler for SpannedHandler {
+    async fn handle<'r>(&self, request: &'r Request<'_>, data: Data<'r>) -> route::Outcome<'r> {
+        let span = request_span(request).clone();
+        self.0.handle(request, data).instrument(span).await
+    }
+}
+
+// Wraps the handlers of routes so that everything logged while handling a
+// request carries its span
+pub fn instrument(routes: Vec<Route>) -> Vec<Route> {
+    routes
+        .into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(SpannedHandler(route.handler));
+            route
+        })
+        .collect()
+}
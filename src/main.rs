@@ -1,12 +1,120 @@
-use std::{error::Error as StdError, fmt::Display};
+use std::{
+    collections::BTreeSet,
+    error::Error as StdError,
+    fmt::Display,
+    time::{Duration, Instant},
+};
 
 use id_contact_jwt::decrypt_and_verify_auth_result;
-use id_contact_proto::{StartCommRequest, StartCommResponse};
-use rocket::{get, launch, post, routes, serde::json::Json, State};
+use id_contact_proto::{AuthResult, AuthStatus, StartCommRequest, StartCommResponse};
+use josekit::jwe::JweDecrypter;
+use rocket::{
+    catchers,
+    data::{ByteUnit, Limits},
+    figment::Figment,
+    form::Form,
+    http::{ContentType, Status},
+    post, routes,
+    serde::json::Json,
+    Build, Data, FromForm, Responder, Rocket, State,
+};
+use rocket_dyn_templates::Template;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{error, info, warn};
 
+mod access;
+#[cfg(feature = "amqp")]
+mod amqp;
+mod assurance;
+mod auth_results;
+mod caching;
+mod capabilities;
+mod cases;
+mod claims;
+mod client;
+mod clock;
+mod cloudevents;
 mod config;
+mod core_auth;
+mod crash;
+mod dashboard;
+mod deadline;
+mod degraded;
+mod demo;
+mod drain;
+mod duplicates;
+mod events;
+mod expiry;
+mod export;
+mod faults;
+mod fingerprint;
+mod formatting;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod headers;
+mod health;
+mod jti;
+mod jwks;
+#[cfg(feature = "kafka")]
+mod kafka;
+mod key_usage;
+mod keys;
+mod logging;
+mod metadata;
+mod mirror;
+#[cfg(feature = "nats")]
+mod nats;
+mod oidc;
+#[cfg(feature = "outbox")]
+mod outbox;
+mod personas;
+mod provider;
+mod purposes;
+mod recording;
+mod redirect;
+mod reload;
+mod replay;
+mod report;
+mod request_log;
+mod response_signing;
+mod responses;
+mod retry;
+mod saml;
+mod self_test;
+mod session;
+mod stats;
+mod ui;
+mod url_signing;
+mod webhook_sink;
+#[cfg(feature = "ws")]
+mod ws;
 
-use config::Config;
+use assurance::AssuranceLevel;
+use auth_results::{AuthResultStore, RequestId};
+use cases::CaseStore;
+use clock::Clock;
+use config::{Config, InvalidAuthResultPolicy};
+use core_auth::{CoreIdentity, CoreSignature};
+use crash::CrashReportDir;
+use deadline::Deadline;
+use drain::Drain;
+use duplicates::DuplicateDetector;
+use events::Events;
+use faults::{FaultInjector, Injected};
+use fingerprint::ConfigFingerprint;
+use headers::{HandledSession, HeaderInjector};
+use jti::JtiCache;
+use key_usage::KeyKind;
+use logging::{Redacted, RedactedAttributes, RequestSpans};
+use mirror::{Mirror, MirrorClient};
+use recording::{Recorder, Recording};
+use reload::LiveConfig;
+use replay::Replay;
+use request_log::RequestLog;
+use response_signing::ResponseSigner;
+use session::{SessionStore, SessionTags, Source};
+use stats::RouteStats;
 
 #[derive(Debug)]
 enum Error {
@@ -14,12 +122,124 @@ enum Error {
     Json(serde_json::Error),
     Utf(std::str::Utf8Error),
     Jwt(id_contact_jwt::Error),
+    Base64(base64::DecodeError),
+    Xml(roxmltree::Error),
+    Io(std::io::Error),
+    Jose(josekit::JoseError),
+    Http(reqwest::Error),
+    PayloadTooLarge,
+    DeadlineExceeded,
+    DuplicateRequest,
+    ProviderNotConfigured,
+    UnknownPersona(String),
+    WebhookSinkNotConfigured,
+    InvalidSignature,
+    LinkExpired,
+    RedirectNotConfigured,
+    ReturnUrlNotAllowed(String),
+    InsufficientAssurance(AssuranceLevel),
+    UnknownPurpose(String),
+    MissingAttributes(Vec<String>),
+    Expired,
+    NotYetValid,
+    UnexpectedIssuer(Option<String>),
+    UnexpectedAudience,
+    ReplayedAuthResult,
+    ClientNotConfigured,
+    ConflictingAuthResult,
+    LoginFailed(String),
+    LoginNotConfigured,
+    UntrustedClient,
+}
+
+impl Error {
+    // Machine readable error category, stable for client test suites to assert on
+    fn code(&self) -> &'static str {
+        match self {
+            Error::Config(_) => "config_error",
+            Error::Json(_) => "invalid_json",
+            Error::Utf(_) => "invalid_utf8",
+            Error::Jwt(_) => "invalid_jwt",
+            Error::Base64(_) => "invalid_base64",
+            Error::Xml(_) => "invalid_xml",
+            Error::Io(_) => "io_error",
+            Error::Jose(_) => "crypto_error",
+            Error::Http(_) => "upstream_error",
+            Error::PayloadTooLarge => "payload_too_large",
+            Error::DeadlineExceeded => "deadline_exceeded",
+            Error::DuplicateRequest => "duplicate_request",
+            Error::ProviderNotConfigured => "provider_not_configured",
+            Error::UnknownPersona(_) => "unknown_persona",
+            Error::WebhookSinkNotConfigured => "webhook_sink_not_configured",
+            Error::InvalidSignature => "invalid_signature",
+            Error::LinkExpired => "link_expired",
+            Error::RedirectNotConfigured => "redirect_not_configured",
+            Error::ReturnUrlNotAllowed(_) => "return_url_not_allowed",
+            Error::InsufficientAssurance(_) => "insufficient_assurance",
+            Error::UnknownPurpose(_) => "unknown_purpose",
+            Error::MissingAttributes(_) => "missing_attributes",
+            Error::Expired => "expired",
+            Error::NotYetValid => "not_yet_valid",
+            Error::UnexpectedIssuer(_) => "unexpected_issuer",
+            Error::UnexpectedAudience => "unexpected_audience",
+            Error::ReplayedAuthResult => "replayed_auth_result",
+            Error::ClientNotConfigured => "client_not_configured",
+            Error::ConflictingAuthResult => "conflicting_auth_result",
+            Error::LoginFailed(_) => "login_failed",
+            Error::LoginNotConfigured => "login_not_configured",
+            Error::UntrustedClient => "untrusted_client",
+        }
+    }
+
+    fn status(&self) -> Status {
+        match self {
+            Error::Config(_) | Error::Io(_) | Error::Jose(_) => Status::InternalServerError,
+            Error::Json(_)
+            | Error::Utf(_)
+            | Error::Jwt(_)
+            | Error::Base64(_)
+            | Error::Xml(_)
+            | Error::ReturnUrlNotAllowed(_)
+            | Error::UnknownPurpose(_)
+            | Error::MissingAttributes(_)
+            | Error::Expired
+            | Error::NotYetValid
+            | Error::UnexpectedIssuer(_)
+            | Error::UnexpectedAudience => Status::BadRequest,
+            Error::Http(_) => Status::BadGateway,
+            Error::PayloadTooLarge => Status::PayloadTooLarge,
+            Error::DeadlineExceeded => Status::GatewayTimeout,
+            Error::DuplicateRequest | Error::ReplayedAuthResult | Error::ConflictingAuthResult => {
+                Status::Conflict
+            }
+            Error::ProviderNotConfigured
+            | Error::UnknownPersona(_)
+            | Error::WebhookSinkNotConfigured
+            | Error::RedirectNotConfigured
+            | Error::ClientNotConfigured
+            | Error::LoginNotConfigured => Status::NotFound,
+            Error::InvalidSignature | Error::LoginFailed(_) | Error::UntrustedClient => {
+                Status::Unauthorized
+            }
+            Error::LinkExpired | Error::InsufficientAssurance(_) => Status::Forbidden,
+        }
+    }
 }
 
 impl<'r, 'o: 'r> rocket::response::Responder<'r, 'o> for Error {
     fn respond_to(self, request: &'r rocket::Request<'_>) -> rocket::response::Result<'o> {
-        let debug_error = rocket::response::Debug::from(self);
-        debug_error.respond_to(request)
+        let status = self.status();
+        if status.class().is_server_error() {
+            error!("Error handling request: {}", self);
+        } else {
+            info!("Rejected request: {}", self);
+        }
+
+        let body = serde_json::json!({
+            "error": self.code(),
+            "description": self.to_string(),
+        });
+        (status, Json(body)).respond_to(request)
     }
 }
 
@@ -47,6 +267,36 @@ impl From<id_contact_jwt::Error> for Error {
     }
 }
 
+impl From<base64::DecodeError> for Error {
+    fn from(e: base64::DecodeError) -> Error {
+        Error::Base64(e)
+    }
+}
+
+impl From<roxmltree::Error> for Error {
+    fn from(e: roxmltree::Error) -> Error {
+        Error::Xml(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<josekit::JoseError> for Error {
+    fn from(e: josekit::JoseError) -> Error {
+        Error::Jose(e)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Error {
+        Error::Http(e)
+    }
+}
+
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -54,6 +304,41 @@ impl Display for Error {
             Error::Utf(e) => e.fmt(f),
             Error::Json(e) => e.fmt(f),
             Error::Jwt(e) => e.fmt(f),
+            Error::Base64(e) => e.fmt(f),
+            Error::Xml(e) => e.fmt(f),
+            Error::Io(e) => e.fmt(f),
+            Error::Jose(e) => e.fmt(f),
+            Error::Http(e) => e.fmt(f),
+            Error::PayloadTooLarge => f.write_str("Payload too large"),
+            Error::DeadlineExceeded => f.write_str("Request deadline exceeded"),
+            Error::DuplicateRequest => f.write_str("Duplicate request"),
+            Error::ProviderNotConfigured => f.write_str("No attribute provider configured"),
+            Error::UnknownPersona(name) => write!(f, "Unknown persona: {}", name),
+            Error::WebhookSinkNotConfigured => f.write_str("No webhook sink configured"),
+            Error::InvalidSignature => f.write_str("Missing or invalid signature"),
+            Error::LinkExpired => f.write_str("Link expired"),
+            Error::RedirectNotConfigured => f.write_str("No redirect configured"),
+            Error::ReturnUrlNotAllowed(url) => write!(f, "Return url not allowed: {}", url),
+            Error::InsufficientAssurance(required) => {
+                write!(f, "Assurance level below required level {}", required)
+            }
+            Error::UnknownPurpose(purpose) => write!(f, "Unknown purpose: {}", purpose),
+            Error::MissingAttributes(names) => {
+                write!(f, "Missing required attributes: {}", names.join(", "))
+            }
+            Error::Expired => f.write_str("Auth result expired"),
+            Error::NotYetValid => f.write_str("Auth result not yet valid"),
+            Error::UnexpectedIssuer(Some(iss)) => write!(f, "Unexpected issuer: {}", iss),
+            Error::UnexpectedIssuer(None) => f.write_str("Missing issuer"),
+            Error::UnexpectedAudience => f.write_str("Auth result meant for another audience"),
+            Error::ReplayedAuthResult => f.write_str("Auth result submitted before"),
+            Error::ClientNotConfigured => f.write_str("No outbound client configured"),
+            Error::ConflictingAuthResult => {
+                f.write_str("Session already received a different auth result")
+            }
+            Error::LoginFailed(reason) => write!(f, "Login failed: {}", reason),
+            Error::LoginNotConfigured => f.write_str("No OpenID Connect login configured"),
+            Error::UntrustedClient => f.write_str("Client certificate of an unknown core"),
         }
     }
 }
@@ -65,68 +350,846 @@ impl StdError for Error {
             Error::Utf(e) => Some(e),
             Error::Json(e) => Some(e),
             Error::Jwt(e) => Some(e),
+            Error::Base64(e) => Some(e),
+            Error::Xml(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Jose(e) => Some(e),
+            Error::Http(e) => Some(e),
+            Error::PayloadTooLarge => None,
+            Error::DeadlineExceeded => None,
+            Error::DuplicateRequest => None,
+            Error::ProviderNotConfigured => None,
+            Error::UnknownPersona(_) => None,
+            Error::WebhookSinkNotConfigured => None,
+            Error::InvalidSignature => None,
+            Error::LinkExpired => None,
+            Error::RedirectNotConfigured => None,
+            Error::ReturnUrlNotAllowed(_) => None,
+            Error::InsufficientAssurance(_) => None,
+            Error::UnknownPurpose(_) => None,
+            Error::MissingAttributes(_) => None,
+            Error::Expired => None,
+            Error::NotYetValid => None,
+            Error::UnexpectedIssuer(_) => None,
+            Error::UnexpectedAudience => None,
+            Error::ReplayedAuthResult => None,
+            Error::ClientNotConfigured => None,
+            Error::ConflictingAuthResult => None,
+            Error::LoginFailed(_) => None,
+            Error::LoginNotConfigured => None,
+            Error::UntrustedClient => None,
         }
     }
 }
 
-#[get("/ui")]
-fn ui() -> &'static str {
-    "Communication plugin UI"
+fn is_failed(auth_result: &AuthResult) -> bool {
+    matches!(auth_result.status, AuthStatus::Failed)
 }
 
-#[get("/ui?<result>")]
-fn ui_withparams(result: String, config: &State<Config>) -> Result<&'static str, Error> {
-    println!("Received inline authentication results {:?}", &result);
+// Flags the session as invalid when its purpose requires attributes the
+// auth result lacks. Failed authentications carry no attributes to check.
+fn check_required_attributes(
+    config: &Config,
+    sessions: &SessionStore,
+    session_id: &str,
+    auth_result: &AuthResult,
+) -> Result<(), Error> {
+    let missing = match sessions
+        .get(session_id)
+        .and_then(|session| config.purpose(session.purpose()).cloned())
+    {
+        Some(purpose) if !is_failed(auth_result) => purpose.missing_attributes(auth_result),
+        _ => return Ok(()),
+    };
+    if missing.is_empty() {
+        return Ok(());
+    }
 
-    let session_result =
-        decrypt_and_verify_auth_result(&result, config.validator(), config.decrypter())?;
-    println!("Decoded: {:?}", session_result);
+    let error = Error::MissingAttributes(missing);
+    warn!("Invalid auth result for session {}: {}", session_id, error);
+    sessions.flag_invalid_auth_result(session_id, error.to_string());
+    Err(error)
+}
 
-    Ok(ui())
+// Tries the configured decryption keys until one succeeds, returning the
+// error for the last one otherwise
+fn decode_auth_result(
+    token: &str,
+    config: &Config,
+    events: &Events,
+    clock: &Clock,
+) -> Result<AuthResult, Error> {
+    let kid = keys::jwe_key_id(token);
+    let mut last_error = None;
+    for (decryption_id, decrypter) in config.keyed_decrypters(kid.as_deref()) {
+        // Only pay for the extra decryption when there is a choice of keys
+        let issuer = if config.has_authenticators() {
+            keys::unverified_issuer(token, decrypter)
+        } else {
+            None
+        };
+        let validator = config.validator_for(issuer.as_deref());
+        let validator_id = config.validator_id(issuer.as_deref());
+        match decrypt_and_verify_auth_result(token, validator, decrypter) {
+            Ok(auth_result) => {
+                key_usage::record(KeyKind::Decryption, decryption_id, true);
+                key_usage::record(KeyKind::Verification, validator_id, true);
+                if let Err(e) = validate_claims(token, decrypter, config, clock) {
+                    events.emit("claim_validation_failed", json!({ "error": e.to_string() }));
+                    return Err(e);
+                }
+                return Ok(auth_result);
+            }
+            Err(e) => {
+                // Tell which of the two keys failed
+                let decrypted = keys::unverified_claims(token, decrypter).is_some();
+                key_usage::record(KeyKind::Decryption, decryption_id, decrypted);
+                if decrypted {
+                    key_usage::record(KeyKind::Verification, validator_id, false);
+                }
+                last_error = Some(e);
+            }
+        }
+    }
+    // Config guarantees at least one decryption key
+    let error = last_error.unwrap();
+    events.emit("decryption_failed", json!({ "error": error.to_string() }));
+    Err(Error::Jwt(error))
 }
 
-#[post("/auth_result", data = "<auth_result>")]
-fn attr_url(auth_result: String, config: &State<Config>) -> Result<(), Error> {
-    println!("Received authentication result {:?}", &auth_result);
-    let auth_result =
-        decrypt_and_verify_auth_result(&auth_result, config.validator(), config.decrypter())?;
-    println!("Decoded: {:?}", auth_result);
+// Checks the claims of a verified auth result against the configured
+// expectations
+fn validate_claims(
+    token: &str,
+    decrypter: &dyn JweDecrypter,
+    config: &Config,
+    clock: &Clock,
+) -> Result<(), Error> {
+    let (issuer, audience) = (config.expected_issuer(), config.expected_audience());
+    if config.claim_validation().is_none() && issuer.is_none() && audience.is_none() {
+        return Ok(());
+    }
 
+    // The signature was verified, so the claims can be trusted
+    let claims = keys::unverified_claims(token, decrypter).unwrap_or_default();
+    claims::check_recipient(&claims, issuer, audience)?;
+    if let Some(validation) = config.claim_validation() {
+        validation.validate(&claims, clock)?;
+    }
     Ok(())
 }
 
+// Default limit for auth results, overridable through rocket's `limits.auth_result`
+const AUTH_RESULT_LIMIT: ByteUnit = ByteUnit::Mebibyte(2);
+
+#[allow(clippy::too_many_arguments)]
+#[post("/auth_result?<session>", data = "<auth_result>")]
+async fn attr_url(
+    auth_result: Data<'_>,
+    session: Option<String>,
+    limits: &Limits,
+    config: &Config,
+    mirror: Mirror<'_>,
+    deadline: Deadline,
+    request_id: RequestId,
+    auth_results: &State<AuthResultStore>,
+    recording: Recording<'_>,
+    sessions: &State<SessionStore>,
+    jtis: &State<JtiCache>,
+    clock: Clock,
+) -> Result<String, Error> {
+    let events = sessions.events();
+    let limit = limits.get("auth_result").unwrap_or(AUTH_RESULT_LIMIT);
+    let auth_result = auth_result.open(limit).into_bytes().await?;
+    if !auth_result.is_complete() {
+        return Err(Error::PayloadTooLarge);
+    }
+    mirror.forward(auth_result.to_vec());
+    recording.body(&auth_result);
+    let auth_result = std::str::from_utf8(&auth_result)?;
+
+    info!("Received authentication result {:?}", auth_result);
+    deadline.check()?;
+    let token = auth_result;
+    let auth_result = decode_auth_result(token, config, events, &clock)?;
+    jtis.check(token, config)?;
+    deadline.check()?;
+    info!("Decoded: {}", Redacted(&auth_result));
+    recording.auth_result(&auth_result);
+    if is_failed(&auth_result) {
+        info!("Authentication failed for delivered authentication result");
+    }
+
+    // Links the delivery to the session that handed out this attr_url
+    let session = session.and_then(|id| {
+        let session = sessions.get(&id);
+        if session.is_none() {
+            warn!("Auth result delivered for unknown session {}", id);
+        }
+        session
+    });
+    let assurance_level = match (config.assurance(), &session) {
+        (Some(assurance), Some(session)) => {
+            let level = assurance.level(token, config);
+            assurance.check(session.purpose(), level)?;
+            level
+        }
+        _ => None,
+    };
+    let session_id = session.as_ref().map(|session| session.id());
+    sessions.deliver(
+        &request_id.0,
+        session_id,
+        auth_result.clone(),
+        config.on_duplicate_delivery(),
+        Source::new("attr_url", Some(token)),
+    )?;
+    let checked = match &session {
+        Some(session) => {
+            if let Some(level) = assurance_level {
+                sessions.set_assurance_level(session.id(), level);
+            }
+            check_required_attributes(config, sessions, session.id(), &auth_result)
+        }
+        None => Ok(()),
+    };
+    auth_results.insert(&request_id.0, auth_result);
+    checked?;
+    Ok(request_id.0)
+}
+
+#[derive(Deserialize, Serialize)]
+struct BatchItem {
+    id: String,
+    auth_result: String,
+    // Session to link the auth result to, as with attr_url
+    session: Option<String>,
+}
+
+#[derive(Serialize)]
+struct BatchItemStatus {
+    id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+// Most auth results accepted in a single batch
+const BATCH_LIMIT: usize = 100;
+
+#[post("/auth_results/batch", data = "<batch>")]
+fn attr_url_batch(
+    batch: Json<Vec<BatchItem>>,
+    config: &Config,
+    mirror: Mirror<'_>,
+    recording: Recording<'_>,
+    sessions: &State<SessionStore>,
+    auth_results: &State<AuthResultStore>,
+    jtis: &State<JtiCache>,
+    clock: Clock,
+) -> Result<Json<Vec<BatchItemStatus>>, Error> {
+    info!("Received batch of {} authentication results", batch.len());
+    if batch.len() > BATCH_LIMIT {
+        return Err(Error::PayloadTooLarge);
+    }
+    if let Ok(body) = serde_json::to_vec(&*batch) {
+        recording.body(&body);
+        mirror.forward(body);
+    }
+    let events = sessions.events();
+
+    // Decryption is cpu bound, so spread the items over a thread per core
+    let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut results: Vec<Result<AuthResult, String>> = Vec::with_capacity(batch.len());
+    for chunk in batch.chunks(workers) {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = chunk
+                .iter()
+                .map(|item| {
+                    scope.spawn(move || {
+                        decode_auth_result(&item.auth_result, config, events, &clock)
+                            .map_err(|e| e.to_string())
+                    })
+                })
+                .collect();
+
+            results.extend(handles.into_iter().map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|_| Err("Processing failed".to_string()))
+            }));
+        });
+    }
+
+    // Check, store and link the results as attr_url does, in order of the batch
+    let statuses = batch
+        .iter()
+        .zip(results)
+        .map(|(item, result)| {
+            let failed = |error: String| BatchItemStatus {
+                id: item.id.clone(),
+                session: None,
+                error: Some(error),
+            };
+            let auth_result = match result {
+                Ok(auth_result) => auth_result,
+                Err(error) => return failed(error),
+            };
+            if let Err(e) = jtis.check(&item.auth_result, config) {
+                return failed(e.to_string());
+            }
+            info!("Decoded {}: {}", item.id, Redacted(&auth_result));
+
+            let session = item.session.as_deref().and_then(|id| {
+                let session = sessions.get(id);
+                if session.is_none() {
+                    warn!(
+                        "Auth result {} delivered for unknown session {}",
+                        item.id, id
+                    );
+                }
+                session
+            });
+            let assurance_level = match (config.assurance(), &session) {
+                (Some(assurance), Some(session)) => {
+                    let level = assurance.level(&item.auth_result, config);
+                    if let Err(e) = assurance.check(session.purpose(), level) {
+                        return failed(e.to_string());
+                    }
+                    level
+                }
+                _ => None,
+            };
+
+            let session_id = session.as_ref().map(|session| session.id());
+            if let Err(e) = sessions.deliver(
+                &item.id,
+                session_id,
+                auth_result.clone(),
+                config.on_duplicate_delivery(),
+                Source::new("batch", Some(item.auth_result.as_str())),
+            ) {
+                return failed(e.to_string());
+            }
+            let error = session_id.and_then(|session_id| {
+                if let Some(level) = assurance_level {
+                    sessions.set_assurance_level(session_id, level);
+                }
+                check_required_attributes(config, sessions, session_id, &auth_result)
+                    .err()
+                    .map(|e| e.to_string())
+            });
+            auth_results.insert(&item.id, auth_result);
+            BatchItemStatus {
+                id: item.id.clone(),
+                session: session_id.map(|session_id| session_id.to_string()),
+                error,
+            }
+        })
+        .collect();
+
+    Ok(Json(statuses))
+}
+
+#[derive(FromForm)]
+struct SamlPost {
+    #[field(name = "SAMLResponse")]
+    saml_response: String,
+}
+
+#[allow(clippy::too_many_arguments)]
+#[post("/auth_result/saml?<session>", data = "<saml>")]
+fn saml_attr_url(
+    saml: Form<SamlPost>,
+    session: Option<String>,
+    config: &Config,
+    request_id: RequestId,
+    recording: Recording<'_>,
+    sessions: &State<SessionStore>,
+    auth_results: &State<AuthResultStore>,
+    jtis: &State<JtiCache>,
+) -> Result<String, Error> {
+    info!("Received SAML response {:?}", &saml.saml_response);
+    recording.body(saml.saml_response.as_bytes());
+    // Signatures on assertions aren't verified, so anyone could forge one
+    if !config.allow_unverified_saml() {
+        return Err(Error::InvalidSignature);
+    }
+    // Brokers commonly wrap the encoded response over multiple lines
+    let encoded: String = saml.saml_response.split_whitespace().collect();
+    let response = String::from_utf8(base64::decode(&encoded)?).map_err(|e| e.utf8_error())?;
+    let assertion = saml::parse(&response)?;
+    jtis.check_saml(&assertion, &response, config)?;
+    info!(
+        "Decoded SAML attributes: {}",
+        RedactedAttributes(&assertion.attributes)
+    );
+    let auth_result = AuthResult {
+        status: AuthStatus::Succes,
+        attributes: Some(assertion.attributes),
+        session_url: None,
+    };
+    recording.auth_result(&auth_result);
+
+    // Links the delivery to a session as attr_url does
+    let session = session.and_then(|id| {
+        let session = sessions.get(&id);
+        if session.is_none() {
+            warn!("SAML response delivered for unknown session {}", id);
+        }
+        session
+    });
+    let assurance_level = match (config.assurance(), &session) {
+        (Some(assurance), Some(session)) => {
+            let level = assurance.saml_level(assertion.authn_context.as_deref());
+            assurance.check(session.purpose(), level)?;
+            level
+        }
+        _ => None,
+    };
+    let session_id = session.as_ref().map(|session| session.id());
+    sessions.deliver(
+        &request_id.0,
+        session_id,
+        auth_result.clone(),
+        config.on_duplicate_delivery(),
+        Source::new("saml", None),
+    )?;
+    let checked = match session_id {
+        Some(session_id) => {
+            if let Some(level) = assurance_level {
+                sessions.set_assurance_level(session_id, level);
+            }
+            check_required_attributes(config, sessions, session_id, &auth_result)
+        }
+        None => Ok(()),
+    };
+    auth_results.insert(&request_id.0, auth_result);
+    checked?;
+    Ok(request_id.0)
+}
+
+fn start_communication(
+    request: &StartCommRequest,
+    config: &Config,
+    sessions: &SessionStore,
+) -> Result<StartCommResponse, Error> {
+    let clock = Clock::with_skew(config.clock_skew());
+    let (auth_result, invalid) =
+        decode_inline_auth_result(request, config, sessions.events(), &clock)?;
+    let (_, response) = start_session(
+        request,
+        auth_result,
+        invalid,
+        BTreeSet::new(),
+        clock,
+        config,
+        sessions,
+    )?;
+    Ok(response)
+}
+
+// Returns the decoded auth_result, if any, and with the record policy the
+// reason it was found invalid
+fn decode_inline_auth_result(
+    request: &StartCommRequest,
+    config: &Config,
+    events: &Events,
+    clock: &Clock,
+) -> Result<(Option<AuthResult>, Option<String>), Error> {
+    let auth_result = match &request.auth_result {
+        Some(auth_result) => auth_result,
+        None => return Ok((None, None)),
+    };
+
+    match decode_auth_result(auth_result, config, events, clock) {
+        Ok(auth_result) => {
+            info!("Decoded auth_result: {}", Redacted(&auth_result));
+            if is_failed(&auth_result) {
+                info!("Authentication failed for inline auth_result");
+            }
+            Ok((Some(auth_result), None))
+        }
+        Err(e) => match config.on_invalid_auth_result() {
+            InvalidAuthResultPolicy::Reject => Err(e),
+            InvalidAuthResultPolicy::Ignore => {
+                warn!("Ignoring invalid inline auth_result: {}", e);
+                Ok((None, None))
+            }
+            InvalidAuthResultPolicy::Record => {
+                warn!("Recording invalid inline auth_result: {}", e);
+                Ok((None, Some(e.to_string())))
+            }
+        },
+    }
+}
+
+fn start_session(
+    request: &StartCommRequest,
+    auth_result: Option<AuthResult>,
+    invalid_auth_result: Option<String>,
+    tags: BTreeSet<String>,
+    clock: Clock,
+    config: &Config,
+    sessions: &SessionStore,
+) -> Result<(String, StartCommResponse), Error> {
+    if !config.allows_purpose(&request.purpose) {
+        return Err(Error::UnknownPurpose(request.purpose.clone()));
+    }
+    let purpose = config.purpose(&request.purpose);
+
+    let assurance_level = match (config.assurance(), &auth_result, &request.auth_result) {
+        (Some(assurance), Some(_), Some(token)) => {
+            let level = assurance.level(token, config);
+            assurance.check(&request.purpose, level)?;
+            level
+        }
+        _ => None,
+    };
+
+    let source = Source::new("start_communication", request.auth_result.as_deref());
+    let session_id = sessions.create(&request.purpose, auth_result.clone(), source, clock);
+    if let Some(reason) = invalid_auth_result {
+        sessions.flag_invalid_auth_result(&session_id, reason);
+    }
+    if !tags.is_empty() {
+        sessions.set_tags(&session_id, tags);
+    }
+    if let Some(auth_result) = &auth_result {
+        check_required_attributes(config, sessions, &session_id, auth_result)?;
+    }
+    if let Some(level) = assurance_level {
+        sessions.set_assurance_level(&session_id, level);
+    }
+    info!(
+        "Started session {} for purpose {}",
+        session_id, request.purpose
+    );
+    let session_query = match (config.client_url_handles(), config.client_url_signing()) {
+        (Some(lifetime), _) => format!(
+            "handle={}",
+            sessions.issue_handle(&session_id, lifetime, &clock)
+        ),
+        (None, Some(signing)) => url_signing::signed_query(signing, &session_id, &clock),
+        (None, None) => format!("session={}", session_id),
+    };
+
+    if let Some(template) = config.response(&request.purpose) {
+        let variables = responses::Variables {
+            session_id: &session_id,
+            purpose: &request.purpose,
+            session_query: &session_query,
+            server_url: config.server_url(),
+            internal_url: config.internal_url(),
+        };
+        let response = StartCommResponse {
+            client_url: template.client_url(&variables),
+            attr_url: template.attr_url(&variables),
+        };
+        return Ok((session_id, response));
+    }
+
+    let client_url = format!(
+        "{}{}?{}",
+        config.server_url(),
+        purpose
+            .and_then(|purpose| purpose.client_url_path())
+            .unwrap_or("/ui"),
+        session_query
+    );
+    let use_attr_url = purpose
+        .and_then(|purpose| purpose.use_attr_url())
+        .unwrap_or_else(|| config.use_attr_url());
+    let attr_url = (use_attr_url && request.auth_result == None).then(|| {
+        format!(
+            "{}/auth_result?session={}",
+            config.internal_url(),
+            session_id
+        )
+    });
+    Ok((
+        session_id,
+        StartCommResponse {
+            client_url,
+            attr_url,
+        },
+    ))
+}
+
+#[derive(Responder)]
+enum StartResponse {
+    Ok(Json<StartCommResponse>),
+    Fault(Status),
+    Malformed((ContentType, &'static str)),
+}
+
+#[allow(clippy::too_many_arguments)]
 #[post("/start_communication", data = "<request>")]
-fn start(
+async fn start(
     request: Json<StartCommRequest>,
-    config: &State<Config>,
-) -> Result<Json<StartCommResponse>, Error> {
-    println!("Received communication request {:?}", request);
-    if let Some(auth_result) = &request.auth_result {
-        let auth_result =
-            decrypt_and_verify_auth_result(auth_result, config.validator(), config.decrypter())?;
-        println!("Decoded auth_result: {:?}", auth_result);
-    }
-
-    if config.use_attr_url() && request.auth_result == None {
-        Ok(Json(StartCommResponse {
-            client_url: format!("{}/ui", config.server_url()),
-            attr_url: Some(format!("{}/auth_result", config.internal_url())),
-        }))
-    } else {
-        Ok(Json(StartCommResponse {
-            client_url: format!("{}/ui", config.server_url()),
-            attr_url: None,
-        }))
-    }
-}
-
-#[launch]
-fn rocket() -> _ {
-    let base = rocket::build().mount("/", routes![start, attr_url, ui, ui_withparams,]);
-    let config = base.figment().extract::<Config>().unwrap_or_else(|_| {
+    config: &Config,
+    duplicates: &State<DuplicateDetector>,
+    mirror: Mirror<'_>,
+    signature: CoreSignature,
+    identity: CoreIdentity,
+    deadline: Deadline,
+    sessions: &State<SessionStore>,
+    faults: &State<FaultInjector>,
+    recording: Recording<'_>,
+    tags: SessionTags,
+    handled: HandledSession<'_>,
+    clock: Clock,
+) -> Result<StartResponse, Error> {
+    info!("Received communication request {:?}", request);
+    identity.verify(config)?;
+    signature.verify(config, &serde_json::to_value(&*request)?)?;
+    let body = serde_json::to_vec(&*request)?;
+    if duplicates.check(&body, config, sessions.events()) {
+        return Err(Error::DuplicateRequest);
+    }
+    recording.body(&body);
+    mirror.forward(body);
+
+    match faults.roll(config) {
+        Some(Injected::Error) => return Ok(StartResponse::Fault(Status::InternalServerError)),
+        Some(Injected::Malformed) => {
+            return Ok(StartResponse::Malformed((
+                ContentType::JSON,
+                "{\"client_url\": ",
+            )))
+        }
+        Some(Injected::Hang(duration)) => rocket::tokio::time::sleep(duration).await,
+        None => {}
+    }
+
+    deadline.check()?;
+    let (auth_result, invalid) =
+        decode_inline_auth_result(&request, config, sessions.events(), &clock)?;
+    if let Some(auth_result) = &auth_result {
+        recording.auth_result(auth_result);
+    }
+    let (session_id, response) = start_session(
+        &request,
+        auth_result,
+        invalid,
+        tags.0,
+        clock,
+        config,
+        sessions,
+    )?;
+    handled.set(&session_id);
+    deadline.check()?;
+    Ok(StartResponse::Ok(Json(response)))
+}
+
+fn timed<T>(work: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let result = work();
+    (result, start.elapsed())
+}
+
+// Joins a startup thread, passing on its panic
+fn join<T>(handle: std::thread::ScopedJoinHandle<'_, T>) -> T {
+    handle
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+fn rocket(figment: Figment) -> Rocket<Build> {
+    let base = rocket::custom(figment);
+    logging::init(base.figment());
+    let startup = Instant::now();
+    let (config, parsing) = timed(|| base.figment().extract::<Config>());
+    let config = match config {
+        Ok(config) => config,
         // Drop error value, as it could contain secrets
-        panic!("Failure to parse configuration")
+        Err(_) if degraded::enabled(base.figment()) => return degraded::build(base),
+        Err(_) => panic!("Failure to parse configuration"),
+    };
+    let mut phases = vec![("configuration and keys", parsing)];
+    let connection = config.connection();
+    if connection.http2_prior_knowledge || connection.max_concurrent_streams.is_some() {
+        warn!("HTTP/2 prior knowledge and stream limits are not supported by this Rocket version");
+    }
+
+    let base = base.mount(
+        "/",
+        logging::instrument(crash::catch_panics(routes![
+            start,
+            attr_url,
+            attr_url_batch,
+            saml_attr_url,
+            ui::ui,
+            ui::ui_post,
+            metadata::metadata,
+            redirect::complete,
+            provider::auth_result,
+            provider::generate,
+            client::simulate_start,
+            webhook_sink::receive,
+            webhook_sink::list,
+            capabilities::get,
+            fingerprint::get,
+            auth_results::get,
+            faults::get,
+            faults::set,
+            dashboard::dashboard,
+            oidc::login,
+            oidc::callback,
+            health::health,
+            health::live,
+            health::ready,
+            degraded::version,
+            report::get,
+            keys::jwks,
+            key_usage::list,
+            cases::list,
+            cases::get,
+            session::status,
+            session::set_tags,
+            export::csv,
+            export::ndjson,
+            recording::list,
+            recording::clear,
+            replay::import,
+            replay::clear,
+            retry::deadletters,
+        ])),
+    );
+    let base = base.register("/", catchers![crash::internal_error]);
+    let crash_reports = CrashReportDir(config.crash_report_dir().to_path_buf());
+    crash::install_hook(crash_reports.0.clone());
+    key_usage::init();
+
+    // The remaining startup work is independent, so it runs side by side
+    let warmup = std::env::args().any(|arg| arg == "--warmup");
+    #[cfg(feature = "outbox")]
+    let mut outbox_db = None;
+    std::thread::scope(|scope| {
+        let self_test = config
+            .self_test()
+            .then(|| scope.spawn(|| timed(self_test::run)));
+        let warmup = warmup.then(|| scope.spawn(|| timed(|| health::warm_up(&config))));
+        #[cfg(feature = "outbox")]
+        let outbox = config
+            .outbox()
+            .map(|outbox| scope.spawn(move || timed(|| outbox::Outbox::open(outbox))));
+
+        if let Some(self_test) = self_test {
+            let (result, elapsed) = join(self_test);
+            result.unwrap_or_else(|e| panic!("Crypto self-test failed: {}", e));
+            info!("Crypto self-test passed");
+            phases.push(("crypto self-test", elapsed));
+        }
+        if let Some(warmup) = warmup {
+            let (result, elapsed) = join(warmup);
+            result.unwrap_or_else(|e| panic!("Warmup decryption failed: {}", e));
+            phases.push(("warmup", elapsed));
+        }
+        #[cfg(feature = "outbox")]
+        if let Some(outbox) = outbox {
+            let (result, elapsed) = join(outbox);
+            outbox_db =
+                Some(result.unwrap_or_else(|e| panic!("Failed to open outbox database: {}", e)));
+            phases.push(("outbox database", elapsed));
+        }
     });
+    let phases: Vec<String> = phases
+        .iter()
+        .map(|(phase, elapsed)| format!("{} {:?}", phase, elapsed))
+        .collect();
+    info!(
+        "Startup work done in {:?}: {}",
+        startup.elapsed(),
+        phases.join(", ")
+    );
+
+    let stats = RouteStats::default();
+    let recorder = Recorder::default();
+    let replay = Replay::default();
+    let base = capabilities::attach(base);
+    let base = expiry::attach(base);
+    let base = retry::attach(base);
+    let base = health::attach(base);
+    let base = reload::attach(base)
+        .attach(RequestSpans)
+        .attach(Template::fairing())
+        .attach(stats.clone())
+        .attach(recorder.clone())
+        .attach(replay.clone())
+        .attach(ResponseSigner);
+    let base = base.attach(HeaderInjector).attach(RequestLog::default());
+    #[cfg(feature = "grpc")]
+    let base = grpc::attach(base);
+    #[cfg(feature = "amqp")]
+    let base = amqp::attach(base);
+    #[cfg(feature = "nats")]
+    let base = nats::attach(base);
+    #[cfg(feature = "outbox")]
+    let base = outbox::attach(base);
+    #[cfg(feature = "kafka")]
+    let base = kafka::attach(base);
+    #[cfg(feature = "ws")]
+    let base = ws::attach(base);
 
-    base.manage(config)
+    let fingerprint = ConfigFingerprint::compute(base.figment());
+    let live = LiveConfig::new(config, fingerprint);
+    let config = live.current();
+    let sessions = SessionStore::default().with_cases(CaseStore::new(live.clone()));
+    #[cfg(feature = "outbox")]
+    let sessions = match outbox_db {
+        Some(outbox) => sessions.with_outbox(outbox),
+        None => sessions,
+    };
+    let retries = retry::RetryQueue::open(config.retry_queue());
+    let drain = Drain::new(sessions.events().clone());
+    let base = base.attach(drain.clone());
+    let base = base.manage(MirrorClient::new(drain.callbacks()));
+
+    base.manage(live)
+        .manage(DuplicateDetector::default())
+        .manage(JtiCache::default())
+        .manage(sessions.events().clone())
+        .manage(sessions)
+        .manage(AuthResultStore::default())
+        .manage(oidc::OidcLogins::default())
+        .manage(retries)
+        .manage(FaultInjector::default())
+        .manage(stats)
+        .manage(recorder)
+        .manage(replay)
+        .manage(crash_reports)
+        .manage(drain)
+}
+
+#[rocket::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(|arg| arg.as_str()) == Some("diff-config") {
+        if args.len() != 4 {
+            eprintln!("Usage: {} diff-config <url> <url>", args[0]);
+            std::process::exit(2);
+        }
+        match fingerprint::diff_config(&args[2], &args[3]).await {
+            Ok(true) => std::process::exit(0),
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("Failed to fetch configuration: {}", e);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    if args.get(1).map(|arg| arg.as_str()) == Some("demo") {
+        if let Err(e) = demo::run().await {
+            eprintln!("Demo failed: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Err(e) = rocket(config::figment()).launch().await {
+        error!("Failed to launch: {}", e);
+    }
 }
@@ -0,0 +1,49 @@
+use rocket::get;
+use serde::Serialize;
+
+use crate::{caching::Cached, config::Config, key_usage::KeyKind};
+
+// Key an auth result may be encrypted for or signed with
+#[derive(Serialize)]
+pub struct KeyReference {
+    kid: String,
+    #[serde(rename = "use")]
+    kind: KeyKind,
+}
+
+// Method description in the shape of the core's comm_methods configuration,
+// so a core can register this plugin without manual configuration
+#[derive(Serialize)]
+pub struct Metadata {
+    tag: String,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image_path: Option<String>,
+    start: String,
+    uses_attr_url: bool,
+    accepts_inline_auth_result: bool,
+    // Empty when any purpose is accepted
+    purposes: Vec<String>,
+    keys: Vec<KeyReference>,
+    jwks_uri: String,
+}
+
+#[get("/metadata")]
+pub fn metadata(config: &Config) -> Cached {
+    let metadata = Metadata {
+        tag: config.method_tag().to_string(),
+        name: config.method_name().to_string(),
+        image_path: config.method_image_path().map(|path| path.to_string()),
+        start: format!("{}/start_communication", config.internal_url()),
+        uses_attr_url: config.use_attr_url(),
+        accepts_inline_auth_result: true,
+        purposes: config.purpose_names(),
+        keys: config
+            .key_ids()
+            .into_iter()
+            .map(|(kind, kid)| KeyReference { kid, kind })
+            .collect(),
+        jwks_uri: format!("{}/.well-known/jwks.json", config.server_url()),
+    };
+    Cached::json(&metadata, config.cache_max_age())
+}
@@ -0,0 +1,132 @@
+use std::{
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use rocket::{
+    request::{FromRequest, Outcome},
+    Request,
+};
+use serde::Deserialize;
+use tracing::warn;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MirrorConfig {
+    url: String,
+    #[serde(default)]
+    redact_headers: Vec<String>,
+}
+
+// Headers describing the original connection, which reqwest sets itself
+const CONNECTION_HEADERS: &[&str] = &["host", "content-length"];
+
+// Mirrors to the target of the configuration each request was handled with,
+// so mirroring can be switched on, off or elsewhere by reloading
+pub struct MirrorClient {
+    client: reqwest::Client,
+    // Mirrored requests still being sent
+    pending: Arc<AtomicUsize>,
+}
+
+impl MirrorClient {
+    pub fn new(pending: Arc<AtomicUsize>) -> MirrorClient {
+        MirrorClient {
+            client: reqwest::Client::new(),
+            pending,
+        }
+    }
+}
+
+struct MirroredRequest {
+    method: reqwest::Method,
+    url: String,
+    headers: Vec<(String, String)>,
+}
+
+// Request guard that captures what is needed to mirror the request, so
+// handlers can forward the body they've read
+pub struct Mirror<'r> {
+    target: Option<(&'r MirrorClient, MirroredRequest)>,
+}
+
+impl<'r> Mirror<'r> {
+    pub fn forward(self, body: Vec<u8>) {
+        let (mirror, request) = match self.target {
+            Some(target) => target,
+            None => return,
+        };
+
+        let mut builder = mirror
+            .client
+            .request(request.method, &request.url)
+            .body(body);
+        for (name, value) in request.headers {
+            builder = builder.header(name.as_str(), value);
+        }
+
+        let pending = mirror.pending.clone();
+        pending.fetch_add(1, Ordering::Relaxed);
+        rocket::tokio::spawn(async move {
+            if let Err(e) = builder.send().await {
+                warn!("Failed to mirror request to {}: {}", request.url, e);
+            }
+            pending.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Mirror<'r> {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let (mirror, config) = match (
+            request.rocket().state::<MirrorClient>(),
+            crate::reload::current(request).and_then(|config| config.mirror()),
+        ) {
+            (Some(mirror), Some(config)) => (mirror, config),
+            _ => return Outcome::Success(Mirror { target: None }),
+        };
+
+        let method = match reqwest::Method::from_bytes(request.method().as_str().as_bytes()) {
+            Ok(method) => method,
+            Err(_) => return Outcome::Success(Mirror { target: None }),
+        };
+        let headers = request
+            .headers()
+            .iter()
+            .filter(|header| {
+                !CONNECTION_HEADERS
+                    .iter()
+                    .any(|name| header.name().as_str().eq_ignore_ascii_case(name))
+            })
+            .map(|header| {
+                let name = header.name().as_str().to_string();
+                let redacted = config
+                    .redact_headers
+                    .iter()
+                    .any(|redact| redact.eq_ignore_ascii_case(&name));
+                let value = if redacted {
+                    "REDACTED".to_string()
+                } else {
+                    header.value().to_string()
+                };
+                (name, value)
+            })
+            .collect();
+
+        Outcome::Success(Mirror {
+            target: Some((
+                mirror,
+                MirroredRequest {
+                    method,
+                    url: format!("{}{}", config.url.trim_end_matches('/'), request.uri()),
+                    headers,
+                },
+            )),
+        })
+    }
+}
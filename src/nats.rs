@@ -0,0 +1,73 @@
+use id_contact_proto::StartCommRequest;
+use rocket::{fairing::AdHoc, Build, Rocket};
+use tracing::{error, info, warn};
+
+use crate::{config::Config, reload::LiveConfig, session::SessionStore, Error};
+
+fn handle_message(data: &[u8], config: &Config, sessions: &SessionStore) -> Result<Vec<u8>, Error> {
+    let request = serde_json::from_slice::<StartCommRequest>(data)?;
+    info!("Received NATS communication request {:?}", request);
+
+    let response = crate::start_communication(&request, config, sessions)?;
+    Ok(serde_json::to_vec(&response)?)
+}
+
+// Same body as the error responses of the http interface
+fn error_reply(e: &Error) -> Vec<u8> {
+    serde_json::json!({
+        "error": e.code(),
+        "description": e.to_string(),
+    })
+    .to_string()
+    .into_bytes()
+}
+
+async fn listen(live: LiveConfig, sessions: SessionStore) -> std::io::Result<()> {
+    let config = live.current();
+    let nats = match config.nats() {
+        Some(nats) => nats,
+        None => return Ok(()),
+    };
+
+    let connection = async_nats::connect(&nats.url).await?;
+    let subscription = connection.subscribe(&nats.subject).await?;
+
+    info!("Listening for NATS requests on {}", nats.subject);
+    while let Some(message) = subscription.next().await {
+        match handle_message(&message.data, &live.current(), &sessions) {
+            Ok(response) => message.respond(response).await?,
+            Err(e) => {
+                warn!("Failed to handle NATS request: {}", e);
+                // Requesters would otherwise wait until they time out
+                if let Err(e) = message.respond(error_reply(&e)).await {
+                    warn!("Failed to send NATS error reply: {}", e);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn attach(base: Rocket<Build>) -> Rocket<Build> {
+    base.attach(AdHoc::on_liftoff("NATS listener", |rocket| {
+        Box::pin(async move {
+            let config = match rocket.state::<LiveConfig>() {
+                Some(config) => config.clone(),
+                None => return,
+            };
+            let sessions = match rocket.state::<SessionStore>() {
+                Some(sessions) => sessions.clone(),
+                None => return,
+            };
+
+            if config.current().nats().is_some() {
+                rocket::tokio::spawn(async move {
+                    if let Err(e) = listen(config, sessions).await {
+                        error!("NATS listener failed: {}", e);
+                    }
+                });
+            }
+        })
+    }))
+}
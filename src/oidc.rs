@@ -0,0 +1,233 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use rocket::{
+    get,
+    http::{Cookie, CookieJar, SameSite},
+    response::Redirect,
+    Request, State,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::info;
+
+use crate::{access::Role, config::Config, session::generate_id, Error};
+
+pub const LOGIN_COOKIE: &str = "comm_test_login";
+
+// Login attempts not completed within this time are forgotten
+const PENDING_LIFETIME: Duration = Duration::from_secs(600);
+
+fn default_role() -> Role {
+    Role::Viewer
+}
+
+fn default_login_lifetime() -> u64 {
+    8 * 60 * 60
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OidcConfig {
+    issuer: String,
+    client_id: String,
+    client_secret: String,
+    // Granted to everyone the issuer lets log in
+    #[serde(default = "default_role")]
+    role: Role,
+    // In seconds
+    #[serde(default = "default_login_lifetime")]
+    login_lifetime: u64,
+}
+
+#[derive(Deserialize)]
+struct Discovery {
+    authorization_endpoint: String,
+    token_endpoint: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Deserialize)]
+struct IdTokenClaims {
+    iss: String,
+    aud: Value,
+    exp: u64,
+    nonce: Option<String>,
+    sub: String,
+}
+
+// Pending login attempts and completed logins, by state and cookie value
+#[derive(Default)]
+pub struct OidcLogins {
+    pending: Mutex<HashMap<String, SystemTime>>,
+    logins: Mutex<HashMap<String, (Role, SystemTime)>>,
+}
+
+impl OidcLogins {
+    fn start(&self) -> String {
+        let state = generate_id();
+        let now = SystemTime::now();
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, started_at| {
+            now.duration_since(*started_at).unwrap_or_default() < PENDING_LIFETIME
+        });
+        pending.insert(state.clone(), now);
+        state
+    }
+
+    fn finish(&self, state: &str) -> bool {
+        match self.pending.lock().unwrap().remove(state) {
+            Some(started_at) => {
+                SystemTime::now()
+                    .duration_since(started_at)
+                    .unwrap_or_default()
+                    < PENDING_LIFETIME
+            }
+            None => false,
+        }
+    }
+
+    fn log_in(&self, role: Role, lifetime: u64) -> String {
+        let id = generate_id();
+        let now = SystemTime::now();
+        let expires_at = now
+            .checked_add(Duration::from_secs(lifetime))
+            .unwrap_or(now);
+        let mut logins = self.logins.lock().unwrap();
+        logins.retain(|_, (_, expires_at)| *expires_at > now);
+        logins.insert(id.clone(), (role, expires_at));
+        id
+    }
+
+    fn role(&self, id: &str) -> Option<Role> {
+        match self.logins.lock().unwrap().get(id) {
+            Some((role, expires_at)) if *expires_at > SystemTime::now() => Some(*role),
+            _ => None,
+        }
+    }
+}
+
+// Role of the browser's login, if any, for the access guards
+pub fn login_role(request: &Request<'_>) -> Option<Role> {
+    let cookie = request.cookies().get(LOGIN_COOKIE)?;
+    request.rocket().state::<OidcLogins>()?.role(cookie.value())
+}
+
+fn redirect_uri(config: &Config) -> String {
+    format!("{}/admin/login/callback", config.server_url())
+}
+
+async fn discover(oidc: &OidcConfig) -> Result<Discovery, Error> {
+    let url = format!(
+        "{}/.well-known/openid-configuration",
+        oidc.issuer.trim_end_matches('/')
+    );
+    Ok(reqwest::get(url).await?.error_for_status()?.json().await?)
+}
+
+// The id token comes straight from the token endpoint over TLS, so per
+// OpenID Connect Core 3.1.3.7 its claims are checked but not its signature
+fn validate(oidc: &OidcConfig, id_token: &str, nonce: &str) -> Result<String, Error> {
+    let payload = id_token
+        .split('.')
+        .nth(1)
+        .ok_or_else(|| Error::LoginFailed("malformed id token".to_string()))?;
+    let payload = base64::decode_config(payload, base64::URL_SAFE_NO_PAD)?;
+    let claims: IdTokenClaims = serde_json::from_slice(&payload)?;
+
+    if claims.iss.trim_end_matches('/') != oidc.issuer.trim_end_matches('/') {
+        return Err(Error::LoginFailed(format!(
+            "unexpected issuer {}",
+            claims.iss
+        )));
+    }
+    let audience_matches = match &claims.aud {
+        Value::String(aud) => *aud == oidc.client_id,
+        Value::Array(auds) => auds.iter().any(|aud| *aud == oidc.client_id),
+        _ => false,
+    };
+    if !audience_matches {
+        return Err(Error::LoginFailed("unexpected audience".to_string()));
+    }
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    if claims.exp <= now {
+        return Err(Error::LoginFailed("id token expired".to_string()));
+    }
+    if claims.nonce.as_deref() != Some(nonce) {
+        return Err(Error::LoginFailed("unexpected nonce".to_string()));
+    }
+    Ok(claims.sub)
+}
+
+#[get("/admin/login")]
+pub async fn login(config: &Config, logins: &State<OidcLogins>) -> Result<Redirect, Error> {
+    let oidc = config.admin_oidc().ok_or(Error::LoginNotConfigured)?;
+    let discovery = discover(oidc).await?;
+
+    // The state doubles as nonce, tying the id token to this attempt
+    let state = logins.start();
+    let url = reqwest::Url::parse_with_params(
+        &discovery.authorization_endpoint,
+        &[
+            ("response_type", "code"),
+            ("scope", "openid"),
+            ("client_id", oidc.client_id.as_str()),
+            ("redirect_uri", redirect_uri(config).as_str()),
+            ("state", state.as_str()),
+            ("nonce", state.as_str()),
+        ],
+    )
+    .map_err(|e| Error::LoginFailed(e.to_string()))?;
+    Ok(Redirect::to(url.to_string()))
+}
+
+#[get("/admin/login/callback?<code>&<state>")]
+pub async fn callback(
+    code: String,
+    state: String,
+    config: &Config,
+    logins: &State<OidcLogins>,
+    cookies: &CookieJar<'_>,
+) -> Result<Redirect, Error> {
+    let oidc = config.admin_oidc().ok_or(Error::LoginNotConfigured)?;
+    if !logins.finish(&state) {
+        return Err(Error::LoginFailed("unknown or expired state".to_string()));
+    }
+
+    let discovery = discover(oidc).await?;
+    let response: TokenResponse = reqwest::Client::new()
+        .post(&discovery.token_endpoint)
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code.as_str()),
+            ("redirect_uri", redirect_uri(config).as_str()),
+            ("client_id", oidc.client_id.as_str()),
+            ("client_secret", oidc.client_secret.as_str()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let subject = validate(oidc, &response.id_token, &state)?;
+
+    info!("Admin login by {} with role {:?}", subject, oidc.role);
+    let login = logins.log_in(oidc.role, oidc.login_lifetime);
+    cookies.add(
+        Cookie::build(LOGIN_COOKIE, login)
+            .path("/")
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .finish(),
+    );
+    Ok(Redirect::to("/admin/dashboard"))
+}
@@ -0,0 +1,207 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use id_contact_proto::AuthResult;
+use rocket::{fairing::AdHoc, Build, Rocket};
+use rusqlite::{params, Connection};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::warn;
+
+use crate::{
+    cloudevents::Envelope,
+    logging::redact,
+    reload::LiveConfig,
+    session::{generate_id, Session, SessionStore},
+};
+
+fn default_poll_interval() -> u64 {
+    1000
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OutboxConfig {
+    path: PathBuf,
+    #[serde(default)]
+    webhook_urls: Vec<String>,
+    // Milliseconds between checks for unpublished events
+    #[serde(default = "default_poll_interval")]
+    poll_interval: u64,
+    // Attribute values are redacted in the database and events unless set
+    #[serde(default)]
+    store_attributes: bool,
+}
+
+pub struct Outbox {
+    connection: Mutex<Connection>,
+    store_attributes: bool,
+}
+
+struct PendingEvent {
+    id: i64,
+    event_id: String,
+    payload: Value,
+}
+
+impl Outbox {
+    pub fn open(config: &OutboxConfig) -> rusqlite::Result<Outbox> {
+        let connection = Connection::open(&config.path)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS sessions (
+                id TEXT PRIMARY KEY,
+                purpose TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                auth_result TEXT
+            );
+            CREATE TABLE IF NOT EXISTS outbox (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                event_id TEXT NOT NULL UNIQUE,
+                payload TEXT NOT NULL,
+                published INTEGER NOT NULL DEFAULT 0
+            );",
+        )?;
+        Ok(Outbox {
+            connection: Mutex::new(connection),
+            store_attributes: config.store_attributes,
+        })
+    }
+
+    fn stored_auth_result(&self, auth_result: &AuthResult) -> Value {
+        let mut value = serde_json::to_value(auth_result).unwrap_or(Value::Null);
+        if !self.store_attributes {
+            if let Some(attributes) = value.get_mut("attributes") {
+                redact(attributes);
+            }
+        }
+        value
+    }
+
+    // Persists the session together with an event describing the change, so
+    // that either both or neither survive a crash
+    pub fn record(&self, session: &Session, kind: &str) -> rusqlite::Result<()> {
+        let created_at = session
+            .created_at()
+            .duration_since(UNIX_EPOCH)
+            .map(|t| t.as_secs())
+            .unwrap_or_default() as i64;
+        let auth_result = session
+            .auth_result()
+            .map(|auth_result| self.stored_auth_result(auth_result));
+        let event_id = generate_id();
+        let payload = json!({
+            "event_id": event_id,
+            "kind": kind,
+            "session_id": session.id(),
+            "purpose": session.purpose(),
+            "status": session.status(),
+            "case_id": session.case_id(),
+            "auth_result": auth_result,
+        })
+        .to_string();
+        let auth_result = auth_result.map(|auth_result| auth_result.to_string());
+
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        transaction.execute(
+            "INSERT INTO sessions (id, purpose, created_at, auth_result) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(id) DO UPDATE SET auth_result = excluded.auth_result",
+            params![session.id(), session.purpose(), created_at, auth_result],
+        )?;
+        transaction.execute(
+            "INSERT INTO outbox (event_id, payload) VALUES (?1, ?2)",
+            params![event_id, payload],
+        )?;
+        transaction.commit()
+    }
+
+    fn pending(&self) -> rusqlite::Result<Vec<PendingEvent>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT id, event_id, payload FROM outbox WHERE published = 0 ORDER BY id LIMIT 100",
+        )?;
+        let events = statement
+            .query_map([], |row| {
+                Ok(PendingEvent {
+                    id: row.get(0)?,
+                    event_id: row.get(1)?,
+                    payload: serde_json::from_str(&row.get::<_, String>(2)?).unwrap_or(Value::Null),
+                })
+            })?
+            .collect();
+        events
+    }
+
+    fn mark_published(&self, id: i64) -> rusqlite::Result<()> {
+        self.connection
+            .lock()
+            .unwrap()
+            .execute("UPDATE outbox SET published = 1 WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+}
+
+async fn publish(
+    client: &reqwest::Client,
+    config: &OutboxConfig,
+    envelope: &Envelope,
+    event: &PendingEvent,
+) -> Result<(), reqwest::Error> {
+    let kind = event.payload["kind"].as_str().unwrap_or_default();
+    let session_id = event.payload["session_id"].as_str();
+    for url in &config.webhook_urls {
+        // The event id lets receivers drop redeliveries after a crash mid-publish
+        let request = client.post(url).header("X-Event-Id", &event.event_id);
+        envelope
+            .wrap(request, &event.event_id, kind, session_id, &event.payload)
+            .send()
+            .await?
+            .error_for_status()?;
+    }
+    Ok(())
+}
+
+async fn relay(config: OutboxConfig, envelope: Envelope, outbox: Arc<Outbox>) {
+    let client = reqwest::Client::new();
+    loop {
+        match outbox.pending() {
+            Ok(events) => {
+                for event in events {
+                    // Stop at the first failure to keep events in order
+                    if let Err(e) = publish(&client, &config, &envelope, &event).await {
+                        warn!("Failed to publish event {}: {}", event.event_id, e);
+                        break;
+                    }
+                    if let Err(e) = outbox.mark_published(event.id) {
+                        warn!("Failed to mark event {} published: {}", event.event_id, e);
+                        break;
+                    }
+                }
+            }
+            Err(e) => warn!("Failed to read outbox: {}", e),
+        }
+        rocket::tokio::time::sleep(Duration::from_millis(config.poll_interval)).await;
+    }
+}
+
+pub fn attach(base: Rocket<Build>) -> Rocket<Build> {
+    base.attach(AdHoc::on_liftoff("Outbox relay", |rocket| {
+        Box::pin(async move {
+            let config = match rocket.state::<LiveConfig>() {
+                Some(config) => config.current(),
+                None => return,
+            };
+            let outbox = match rocket.state::<SessionStore>().and_then(|s| s.outbox()) {
+                Some(outbox) => outbox,
+                None => return,
+            };
+
+            let envelope = Envelope::new(&config);
+            if let Some(config) = config.outbox().cloned() {
+                rocket::tokio::spawn(relay(config, envelope, outbox));
+            }
+        })
+    }))
+}
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+use rand::{seq::SliceRandom, Rng};
+
+pub type Persona = HashMap<String, String>;
+
+const FIRST_NAMES: &[&str] = &[
+    "Daan", "Sem", "Lucas", "Levi", "Finn", "Emma", "Julia", "Mila", "Tess", "Sophie", "Anna",
+    "Noah", "Fenna", "Bram", "Lotte", "Jesse", "Sanne", "Thijs", "Eva", "Ruben",
+];
+
+const LAST_NAMES: &[&str] = &[
+    "de Jong",
+    "Jansen",
+    "de Vries",
+    "van den Berg",
+    "van Dijk",
+    "Bakker",
+    "Janssen",
+    "Visser",
+    "Smit",
+    "Meijer",
+    "de Boer",
+    "Mulder",
+    "de Groot",
+    "Bos",
+    "Vos",
+    "Peters",
+    "Hendriks",
+    "van Leeuwen",
+    "Dekker",
+    "Brouwer",
+];
+
+const STREETS: &[&str] = &[
+    "Kerkstraat",
+    "Schoolstraat",
+    "Molenweg",
+    "Dorpsstraat",
+    "Stationsweg",
+    "Julianastraat",
+    "Beatrixlaan",
+    "Nieuwstraat",
+    "Marktplein",
+    "Lindelaan",
+];
+
+const CITIES: &[&str] = &[
+    "Amsterdam",
+    "Rotterdam",
+    "Den Haag",
+    "Utrecht",
+    "Eindhoven",
+    "Groningen",
+    "Tilburg",
+    "Almere",
+    "Breda",
+    "Nijmegen",
+    "Apeldoorn",
+    "Haarlem",
+    "Arnhem",
+    "Enschede",
+    "Zwolle",
+];
+
+fn pick<R: Rng>(rng: &mut R, options: &[&str]) -> String {
+    options.choose(rng).unwrap_or(&"").to_string()
+}
+
+// Generates a BSN satisfying the eleven test, so it passes format validation
+fn bsn<R: Rng>(rng: &mut R) -> String {
+    loop {
+        let digits: Vec<u32> = (0..8).map(|_| rng.gen_range(0..10)).collect();
+        let sum: u32 = digits
+            .iter()
+            .zip((2..=9).rev())
+            .map(|(digit, weight)| digit * weight)
+            .sum();
+        let check = sum % 11;
+        if digits[0] != 0 && check < 10 {
+            return digits
+                .iter()
+                .chain(std::iter::once(&check))
+                .map(|digit| digit.to_string())
+                .collect();
+        }
+    }
+}
+
+pub fn generate() -> Persona {
+    let mut rng = rand::thread_rng();
+
+    let first_name = pick(&mut rng, FIRST_NAMES);
+    let last_name = pick(&mut rng, LAST_NAMES);
+    let email = format!(
+        "{}.{}@example.nl",
+        first_name.to_lowercase(),
+        last_name.to_lowercase().replace(' ', "")
+    );
+
+    let mut persona = Persona::new();
+    persona.insert("firstnames".to_string(), first_name);
+    persona.insert("lastname".to_string(), last_name);
+    persona.insert("email".to_string(), email);
+    persona.insert(
+        "phone".to_string(),
+        format!("+316{:08}", rng.gen_range(0..100_000_000)),
+    );
+    persona.insert(
+        "street".to_string(),
+        format!("{} {}", pick(&mut rng, STREETS), rng.gen_range(1..200)),
+    );
+    persona.insert(
+        "postal_code".to_string(),
+        format!(
+            "{} {}{}",
+            rng.gen_range(1000..10000),
+            rng.gen_range(b'A'..=b'Z') as char,
+            rng.gen_range(b'A'..=b'Z') as char
+        ),
+    );
+    persona.insert("city".to_string(), pick(&mut rng, CITIES));
+    persona.insert(
+        "date_of_birth".to_string(),
+        format!(
+            "{}-{:02}-{:02}",
+            rng.gen_range(1930..2004),
+            rng.gen_range(1..=12),
+            rng.gen_range(1..=28)
+        ),
+    );
+    persona.insert("bsn".to_string(), bsn(&mut rng));
+    persona
+}
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use id_contact_jwt::sign_and_encrypt_auth_result;
+use id_contact_proto::{AuthResult, AuthStatus};
+use rocket::{post, serde::json::Json};
+use serde::Deserialize;
+use tracing::info;
+
+use crate::{access::Operator, config::Config, logging::RedactedAttributes, personas, Error};
+
+#[derive(Deserialize, Debug)]
+pub struct ProviderRequest {
+    // Configured persona to use, a freshly generated one if absent
+    persona: Option<String>,
+    // Subset of the persona's attributes to disclose, all if absent
+    attributes: Option<Vec<String>>,
+}
+
+#[post("/provider/auth_result", data = "<request>")]
+pub fn auth_result(request: Json<ProviderRequest>, config: &Config) -> Result<String, Error> {
+    info!("Received attribute provider request {:?}", request);
+    let provider = config
+        .attribute_provider()
+        .ok_or(Error::ProviderNotConfigured)?;
+    let persona = match &request.persona {
+        Some(name) => config
+            .persona(name)
+            .cloned()
+            .ok_or_else(|| Error::UnknownPersona(name.clone()))?,
+        None => personas::generate(),
+    };
+
+    let attributes: HashMap<String, String> = match &request.attributes {
+        Some(requested) => persona
+            .into_iter()
+            .filter(|(name, _)| requested.contains(name))
+            .collect(),
+        None => persona,
+    };
+
+    let auth_result = AuthResult {
+        status: AuthStatus::Succes,
+        attributes: Some(attributes),
+        session_url: None,
+    };
+    Ok(sign_and_encrypt_auth_result(
+        &auth_result,
+        provider.signer(),
+        provider.encrypter(),
+    )?)
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum GeneratedStatus {
+    Success,
+    Failed,
+}
+
+impl Default for GeneratedStatus {
+    fn default() -> GeneratedStatus {
+        GeneratedStatus::Success
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GenerateRequest {
+    #[serde(default)]
+    status: GeneratedStatus,
+    #[serde(default)]
+    attributes: HashMap<String, String>,
+}
+
+// Auth result with exactly the given contents, signed and encrypted with
+// the attribute provider keys, so test suites need no JWT tooling of their own
+#[post("/internal/generate_auth_result", data = "<request>")]
+pub fn generate(
+    _operator: Operator,
+    request: Json<GenerateRequest>,
+    config: &Config,
+) -> Result<String, Error> {
+    info!(
+        "Generating {:?} auth result with {}",
+        request.status,
+        RedactedAttributes(&request.attributes)
+    );
+    let provider = config
+        .attribute_provider()
+        .ok_or(Error::ProviderNotConfigured)?;
+
+    let GenerateRequest { status, attributes } = request.into_inner();
+    let auth_result = match status {
+        GeneratedStatus::Success => AuthResult {
+            status: AuthStatus::Succes,
+            attributes: Some(attributes),
+            session_url: None,
+        },
+        GeneratedStatus::Failed => AuthResult {
+            status: AuthStatus::Failed,
+            attributes: None,
+            session_url: None,
+        },
+    };
+    Ok(sign_and_encrypt_auth_result(
+        &auth_result,
+        provider.signer(),
+        provider.encrypter(),
+    )?)
+}
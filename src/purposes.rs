@@ -0,0 +1,38 @@
+use id_contact_proto::AuthResult;
+use serde::Deserialize;
+
+// Behaviour for a single purpose. Once any purpose is configured, requests
+// for purposes not listed are rejected.
+#[derive(Deserialize, Debug, Clone)]
+pub struct PurposeConfig {
+    // Path of the client_url relative to server_url, instead of /ui
+    client_url_path: Option<String>,
+    // Overrides the global use_attr_url
+    use_attr_url: Option<bool>,
+    #[serde(default)]
+    required_attributes: Vec<String>,
+}
+
+impl PurposeConfig {
+    pub fn client_url_path(&self) -> Option<&str> {
+        self.client_url_path.as_deref()
+    }
+
+    pub fn use_attr_url(&self) -> Option<bool> {
+        self.use_attr_url
+    }
+
+    // Required attributes the auth result lacks
+    pub fn missing_attributes(&self, auth_result: &AuthResult) -> Vec<String> {
+        self.required_attributes
+            .iter()
+            .filter(|name| {
+                !auth_result
+                    .attributes
+                    .as_ref()
+                    .map_or(false, |attributes| attributes.contains_key(*name))
+            })
+            .cloned()
+            .collect()
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    convert::Infallible,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use id_contact_proto::AuthResult;
+use rocket::{
+    delete,
+    fairing::{Fairing, Info, Kind},
+    get,
+    request::{FromRequest, Outcome},
+    serde::json::Json,
+    Request, Response, State,
+};
+use serde::Serialize;
+
+use crate::access::{Admin, Viewer};
+
+#[derive(Serialize, Clone)]
+pub struct RecordedRequest {
+    received_at: u64,
+    method: String,
+    uri: String,
+    headers: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    auth_result: Option<AuthResult>,
+    // How a received webhook's signature was verified
+    #[serde(skip_serializing_if = "Option::is_none")]
+    verified_by: Option<&'static str>,
+    status: u16,
+}
+
+impl RecordedRequest {
+    // Copy fit for viewers: bodies may hold plaintext auth results, and
+    // attribute values are hidden as in the log
+    fn redacted(&self) -> RecordedRequest {
+        let mut recorded = self.clone();
+        recorded.body = recorded.body.map(|_| "[redacted]".to_string());
+        if let Some(attributes) = recorded
+            .auth_result
+            .as_mut()
+            .and_then(|auth_result| auth_result.attributes.as_mut())
+        {
+            for value in attributes.values_mut() {
+                *value = "[redacted]".to_string();
+            }
+        }
+        recorded
+    }
+
+    pub fn received_at(&self) -> u64 {
+        self.received_at
+    }
+
+    pub fn body(&self) -> &str {
+        self.body.as_deref().unwrap_or_default()
+    }
+
+    pub fn verified_by(&self) -> Option<&'static str> {
+        self.verified_by
+    }
+}
+
+// Filled in by handlers through the Recording guard, as only they get to
+// see the request body and decoded auth result
+#[derive(Default)]
+struct RecordingSlot {
+    body: Mutex<Option<String>>,
+    auth_result: Mutex<Option<AuthResult>>,
+    verified_by: Mutex<Option<&'static str>>,
+}
+
+pub struct Recording<'r>(&'r RecordingSlot);
+
+impl<'r> Recording<'r> {
+    pub fn body(&self, body: &[u8]) {
+        *self.0.body.lock().unwrap() = Some(String::from_utf8_lossy(body).into_owned());
+    }
+
+    pub fn auth_result(&self, auth_result: &AuthResult) {
+        *self.0.auth_result.lock().unwrap() = Some(auth_result.clone());
+    }
+
+    pub fn verified_by(&self, verified_by: &'static str) {
+        *self.0.verified_by.lock().unwrap() = Some(verified_by);
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Recording<'r> {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(Recording(request.local_cache(RecordingSlot::default)))
+    }
+}
+
+#[derive(Serialize)]
+pub struct RecordingSummary {
+    requests: usize,
+    by_status: BTreeMap<u16, usize>,
+    with_auth_result: usize,
+}
+
+// Shared between the recording fairing and managed state. The capacity is
+// taken from the configuration of each request, so it follows reloads.
+#[derive(Clone, Default)]
+pub struct Recorder {
+    requests: Arc<Mutex<VecDeque<RecordedRequest>>>,
+}
+
+impl Recorder {
+    // Recorded requests to the given path that were handled successfully
+    pub fn successful(&self, method: &str, path: &str) -> Vec<RecordedRequest> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|request| {
+                request.method == method && request.uri == path && request.status < 300
+            })
+            .cloned()
+            .collect()
+    }
+
+    pub fn summary(&self) -> RecordingSummary {
+        let requests = self.requests.lock().unwrap();
+        let mut by_status = BTreeMap::new();
+        for request in requests.iter() {
+            *by_status.entry(request.status).or_insert(0) += 1;
+        }
+        RecordingSummary {
+            requests: requests.len(),
+            by_status,
+            with_auth_result: requests
+                .iter()
+                .filter(|request| request.auth_result.is_some())
+                .count(),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for Recorder {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request recorder",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        // Don't let reading the recordings fill up the recordings
+        if request.uri().path().as_str() == "/internal/recorded_requests" {
+            return;
+        }
+
+        let slot = request.local_cache(RecordingSlot::default);
+        let recorded = RecordedRequest {
+            received_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|t| t.as_secs())
+                .unwrap_or_default(),
+            method: request.method().to_string(),
+            // Query strings may hold auth results, so only the path is kept
+            uri: request.uri().path().to_string(),
+            headers: request
+                .headers()
+                .iter()
+                .map(|header| {
+                    // Admin tokens would otherwise be readable by any viewer
+                    let value = if header.name() == "Authorization" {
+                        "[redacted]".to_string()
+                    } else {
+                        header.value().to_string()
+                    };
+                    (header.name().to_string(), value)
+                })
+                .collect(),
+            body: slot.body.lock().unwrap().take(),
+            auth_result: slot.auth_result.lock().unwrap().take(),
+            verified_by: *slot.verified_by.lock().unwrap(),
+            status: response.status().code,
+        };
+
+        let capacity = crate::reload::current(request)
+            .map(|config| config.recording_capacity())
+            .unwrap_or_default();
+        let mut requests = self.requests.lock().unwrap();
+        while !requests.is_empty() && requests.len() >= capacity {
+            requests.pop_front();
+        }
+        if capacity > 0 {
+            requests.push_back(recorded);
+        }
+    }
+}
+
+// Bodies and attribute values are only shown to admins
+#[get("/internal/recorded_requests")]
+pub fn list(
+    _viewer: Viewer,
+    admin: Option<Admin>,
+    recorder: &State<Recorder>,
+) -> Json<Vec<RecordedRequest>> {
+    let requests = recorder.requests.lock().unwrap();
+    Json(match admin {
+        Some(_) => requests.iter().cloned().collect(),
+        None => requests.iter().map(RecordedRequest::redacted).collect(),
+    })
+}
+
+#[delete("/internal/recorded_requests")]
+pub fn clear(_admin: Admin, recorder: &State<Recorder>) {
+    recorder.requests.lock().unwrap().clear();
+}
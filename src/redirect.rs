@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use josekit::{
+    jws::{JwsHeader, HS256},
+    jwt::{self, JwtPayload},
+};
+use rocket::{get, response::Redirect};
+use tracing::info;
+
+use crate::{clock::Clock, config::Config, Error};
+
+const TOKEN_VALIDITY: Duration = Duration::from_secs(5 * 60);
+
+fn completion_token(secret: &str, clock: &Clock) -> Result<String, Error> {
+    let mut header = JwsHeader::new();
+    header.set_token_type("JWT");
+
+    let now = clock.now();
+    let mut payload = JwtPayload::new();
+    payload.set_subject("comm-test-completion");
+    payload.set_issued_at(&now);
+    payload.set_expires_at(&(now + TOKEN_VALIDITY));
+
+    let signer = HS256.signer_from_bytes(secret.as_bytes())?;
+    Ok(jwt::encode_with_signer(&payload, &header, &signer)?)
+}
+
+#[get("/ui/complete?<return_url>")]
+pub fn complete(
+    return_url: Option<String>,
+    config: &Config,
+    clock: Clock,
+) -> Result<Redirect, Error> {
+    let redirect = config.redirect().ok_or(Error::RedirectNotConfigured)?;
+
+    // Request-provided urls must be allow-listed, to avoid acting as an open redirect
+    let return_url = match return_url {
+        Some(url) if redirect.allowed_urls.contains(&url) => url,
+        Some(url) => return Err(Error::ReturnUrlNotAllowed(url)),
+        None => redirect
+            .default_url
+            .clone()
+            .ok_or(Error::RedirectNotConfigured)?,
+    };
+
+    let separator = if return_url.contains('?') { '&' } else { '?' };
+    let target = format!(
+        "{}{}token={}",
+        return_url,
+        separator,
+        completion_token(&redirect.secret, &clock)?
+    );
+    info!("Redirecting user to {}", target);
+
+    Ok(Redirect::to(target))
+}
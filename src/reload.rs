@@ -0,0 +1,146 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use rocket::{
+    fairing::AdHoc,
+    http::Status,
+    request::{FromRequest, Outcome},
+    Build, Orbit, Request, Rocket,
+};
+use tracing::{info, warn};
+
+use crate::{config::Config, fingerprint::ConfigFingerprint};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+struct Live {
+    config: Arc<Config>,
+    fingerprint: ConfigFingerprint,
+}
+
+// The current configuration, swapped out as a whole on reload along with
+// its fingerprint. Requests hold on to the version they started with, see
+// current below.
+#[derive(Clone)]
+pub struct LiveConfig(Arc<RwLock<Live>>);
+
+impl LiveConfig {
+    pub fn new(config: Config, fingerprint: ConfigFingerprint) -> LiveConfig {
+        LiveConfig(Arc::new(RwLock::new(Live {
+            config: Arc::new(config),
+            fingerprint,
+        })))
+    }
+
+    pub fn current(&self) -> Arc<Config> {
+        self.0.read().unwrap().config.clone()
+    }
+
+    pub fn fingerprint(&self) -> ConfigFingerprint {
+        self.0.read().unwrap().fingerprint.clone()
+    }
+
+    fn replace(&self, config: Config, fingerprint: ConfigFingerprint) {
+        *self.0.write().unwrap() = Live {
+            config: Arc::new(config),
+            fingerprint,
+        };
+    }
+}
+
+// The configuration as of the first time it was asked for during the
+// request, so a reload halfway doesn't mix old and new keys
+pub fn current<'r>(request: &'r Request<'_>) -> Option<&'r Config> {
+    request
+        .local_cache(|| {
+            request
+                .rocket()
+                .state::<LiveConfig>()
+                .map(LiveConfig::current)
+        })
+        .as_deref()
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for &'r Config {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match current(request) {
+            Some(config) => Outcome::Success(config),
+            None => Outcome::Failure((Status::InternalServerError, ())),
+        }
+    }
+}
+
+// Same lookup as rocket::Config::figment
+fn config_path() -> PathBuf {
+    PathBuf::from(std::env::var("ROCKET_CONFIG").unwrap_or_else(|_| "Rocket.toml".to_string()))
+}
+
+fn modified() -> Option<SystemTime> {
+    std::fs::metadata(config_path()).ok()?.modified().ok()
+}
+
+fn reload(live: &LiveConfig, reason: &str) {
+    let figment = crate::config::figment();
+    match figment.extract::<Config>() {
+        Ok(config) => {
+            live.replace(config, ConfigFingerprint::compute(&figment));
+            info!("Reloaded configuration after {}", reason);
+        }
+        // Keep serving with the previous configuration
+        Err(e) => warn!("Failed to reload configuration after {}: {}", reason, e),
+    }
+}
+
+async fn watch_file(live: LiveConfig) {
+    let mut last_modified = modified();
+    loop {
+        rocket::tokio::time::sleep(POLL_INTERVAL).await;
+        let modified = modified();
+        if modified != last_modified {
+            last_modified = modified;
+            reload(&live, "file change");
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn watch_signal(live: LiveConfig) {
+    use rocket::tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            warn!("Failed to listen for SIGHUP: {}", e);
+            return;
+        }
+    };
+    while hangups.recv().await.is_some() {
+        reload(&live, "SIGHUP");
+    }
+}
+
+fn start(rocket: &Rocket<Orbit>) {
+    let live = match rocket.state::<LiveConfig>() {
+        Some(live) if live.current().config_reload() => live.clone(),
+        _ => return,
+    };
+    info!(
+        "Watching {} for configuration changes",
+        config_path().display()
+    );
+    #[cfg(unix)]
+    rocket::tokio::spawn(watch_signal(live.clone()));
+    rocket::tokio::spawn(watch_file(live));
+}
+
+pub fn attach(base: Rocket<Build>) -> Rocket<Build> {
+    base.attach(AdHoc::on_liftoff("Configuration reload", |rocket| {
+        Box::pin(async move { start(rocket) })
+    }))
+}
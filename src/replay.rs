@@ -0,0 +1,222 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::Cursor,
+    sync::{Arc, Mutex},
+};
+
+use rocket::{
+    delete,
+    fairing::{Fairing, Info, Kind},
+    http::{Header, Status},
+    post,
+    serde::json::Json,
+    Request, Response, State,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::access::Operator;
+
+// Headers describing the original transfer, which don't apply to the
+// replayed body
+const TRANSFER_HEADERS: &[&str] = &["content-length", "transfer-encoding", "content-encoding"];
+
+#[derive(Deserialize)]
+struct HarHeader {
+    name: String,
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct HarContent {
+    text: Option<String>,
+    encoding: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct HarResponse {
+    status: u16,
+    #[serde(default)]
+    headers: Vec<HarHeader>,
+    content: Option<HarContent>,
+}
+
+#[derive(Deserialize)]
+struct HarRequest {
+    method: String,
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct HarEntry {
+    request: HarRequest,
+    response: HarResponse,
+}
+
+#[derive(Deserialize)]
+pub struct HarLog {
+    entries: Vec<HarEntry>,
+}
+
+// As served by /internal/recorded_requests, which doesn't keep response
+// bodies, so only the status is replayed
+#[derive(Deserialize)]
+pub struct RecordedEntry {
+    method: String,
+    uri: String,
+    status: u16,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum Import {
+    Har { log: HarLog },
+    Recorded(Vec<RecordedEntry>),
+}
+
+struct CapturedResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+fn path_of(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(url) => url.path().to_string(),
+        // Already relative
+        Err(_) => url.split('?').next().unwrap_or_default().to_string(),
+    }
+}
+
+impl HarEntry {
+    fn into_captured(self) -> ((String, String), CapturedResponse) {
+        let content = self.response.content;
+        let text = content
+            .as_ref()
+            .and_then(|content| content.text.clone())
+            .unwrap_or_default();
+        let base64 = content.and_then(|content| content.encoding).as_deref() == Some("base64");
+        let body = if base64 {
+            base64::decode(&text).unwrap_or_else(|_| text.into_bytes())
+        } else {
+            text.into_bytes()
+        };
+        let headers = self
+            .response
+            .headers
+            .into_iter()
+            .filter(|header| {
+                !TRANSFER_HEADERS
+                    .iter()
+                    .any(|name| header.name.eq_ignore_ascii_case(name))
+            })
+            .map(|header| (header.name, header.value))
+            .collect();
+        (
+            (
+                self.request.method.to_uppercase(),
+                path_of(&self.request.url),
+            ),
+            CapturedResponse {
+                status: self.response.status,
+                headers,
+                body,
+            },
+        )
+    }
+}
+
+// Captured responses per method and path, served in the order they were
+// captured. Once a route runs out, its requests are handled normally again.
+#[derive(Clone, Default)]
+pub struct Replay {
+    responses: Arc<Mutex<HashMap<(String, String), VecDeque<CapturedResponse>>>>,
+}
+
+#[derive(Serialize)]
+pub struct ImportSummary {
+    imported: usize,
+}
+
+#[rocket::async_trait]
+impl Fairing for Replay {
+    fn info(&self) -> Info {
+        Info {
+            name: "Recorded traffic replay",
+            kind: Kind::Response,
+        }
+    }
+
+    // Handlers still run, so their side effects, like starting sessions, are
+    // kept; only what the client gets to see is replaced
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let key = (
+            request.method().as_str().to_string(),
+            request.uri().path().to_string(),
+        );
+        let captured = match self.responses.lock().unwrap().get_mut(&key) {
+            Some(captured) => captured.pop_front(),
+            None => return,
+        };
+        let captured = match captured {
+            Some(captured) => captured,
+            None => return,
+        };
+
+        info!("Replaying captured response for {} {}", key.0, key.1);
+        let status = Status::from_code(captured.status).unwrap_or_else(|| {
+            warn!("Captured unknown status {}", captured.status);
+            Status::InternalServerError
+        });
+        let mut replayed = Response::build();
+        replayed
+            .status(status)
+            .sized_body(captured.body.len(), Cursor::new(captured.body));
+        for (name, value) in captured.headers {
+            replayed.raw_header_adjoin(name, value);
+        }
+        response.merge(replayed.finalize());
+        response.set_header(Header::new("X-Replayed", "true"));
+    }
+}
+
+#[post("/admin/recordings/import", data = "<import>")]
+pub fn import(
+    _operator: Operator,
+    import: Json<Import>,
+    replay: &State<Replay>,
+) -> Json<ImportSummary> {
+    let captured: Vec<_> = match import.into_inner() {
+        Import::Har { log } => log
+            .entries
+            .into_iter()
+            .map(HarEntry::into_captured)
+            .collect(),
+        Import::Recorded(entries) => entries
+            .into_iter()
+            .map(|entry| {
+                (
+                    (entry.method.to_uppercase(), path_of(&entry.uri)),
+                    CapturedResponse {
+                        status: entry.status,
+                        headers: Vec::new(),
+                        body: Vec::new(),
+                    },
+                )
+            })
+            .collect(),
+    };
+
+    let imported = captured.len();
+    let mut responses = replay.responses.lock().unwrap();
+    for (key, response) in captured {
+        responses.entry(key).or_default().push_back(response);
+    }
+    info!("Imported {} captured responses for replay", imported);
+    Json(ImportSummary { imported })
+}
+
+#[delete("/admin/recordings/import")]
+pub fn clear(_operator: Operator, replay: &State<Replay>) {
+    replay.responses.lock().unwrap().clear();
+}
@@ -0,0 +1,108 @@
+use std::{
+    collections::BTreeMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rocket::{get, serde::json::Json, Responder, State};
+use rocket_dyn_templates::Template;
+use serde::Serialize;
+
+use crate::{
+    access::Viewer,
+    faults::{Fault, FaultInjector},
+    fingerprint::ConfigFingerprint,
+    jti::JtiCache,
+    recording::{Recorder, RecordingSummary},
+    reload::LiveConfig,
+    session::SessionStore,
+    stats::{RouteCounts, RouteStats},
+};
+
+// Bumped whenever fields are removed or change meaning, so tooling
+// processing stored reports can tell them apart
+const REPORT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+pub struct SessionSummary {
+    total: usize,
+    with_auth_result: usize,
+    failed: usize,
+}
+
+#[derive(Serialize)]
+pub struct Report {
+    report_version: u32,
+    plugin_version: &'static str,
+    generated_at: u64,
+    config: ConfigFingerprint,
+    sessions: SessionSummary,
+    routes: BTreeMap<String, RouteCounts>,
+    recordings: RecordingSummary,
+    faults: Vec<Fault>,
+    panics: u64,
+    replayed_auth_results: u64,
+    dropped_events: u64,
+}
+
+#[derive(Responder)]
+pub enum ReportResponse {
+    Json(Json<Report>),
+    Html(Template),
+}
+
+fn summarize_sessions(sessions: &SessionStore, tag: Option<&str>) -> SessionSummary {
+    let sessions: Vec<_> = sessions
+        .list()
+        .into_iter()
+        .filter(|session| session.tagged(tag))
+        .collect();
+    SessionSummary {
+        total: sessions.len(),
+        with_auth_result: sessions
+            .iter()
+            .filter(|session| session.auth_result().is_some())
+            .count(),
+        failed: sessions
+            .iter()
+            .filter(|session| session.auth_result().map_or(false, crate::is_failed))
+            .count(),
+    }
+}
+
+// Everything known about the current test run in one document, meant to
+// be stored as a CI artifact
+#[allow(clippy::too_many_arguments)]
+#[get("/admin/report?<format>&<tag>")]
+pub fn get(
+    _viewer: Viewer,
+    format: Option<&str>,
+    tag: Option<&str>,
+    live: &State<LiveConfig>,
+    sessions: &State<SessionStore>,
+    stats: &State<RouteStats>,
+    recorder: &State<Recorder>,
+    faults: &State<FaultInjector>,
+    jtis: &State<JtiCache>,
+) -> ReportResponse {
+    let report = Report {
+        report_version: REPORT_VERSION,
+        plugin_version: env!("CARGO_PKG_VERSION"),
+        generated_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        config: live.fingerprint(),
+        sessions: summarize_sessions(sessions, tag),
+        routes: stats.routes(),
+        recordings: recorder.summary(),
+        faults: faults.active(&live.current()),
+        panics: crate::crash::panic_count(),
+        replayed_auth_results: jtis.rejected(),
+        dropped_events: sessions.events().dropped(),
+    };
+
+    match format {
+        Some("html") => ReportResponse::Html(Template::render("report", &report)),
+        _ => ReportResponse::Json(Json(report)),
+    }
+}
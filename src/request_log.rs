@@ -0,0 +1,163 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    tokio::{
+        fs::{self, File, OpenOptions},
+        io::AsyncWriteExt,
+        sync::mpsc,
+    },
+    Data, Orbit, Request, Response, Rocket,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::reload::LiveConfig;
+
+// Entries beyond this are dropped rather than slowing down request handling
+const QUEUE_SIZE: usize = 4096;
+
+fn default_max_size() -> u64 {
+    10 * 1024 * 1024
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RequestLogConfig {
+    path: PathBuf,
+    #[serde(default = "default_max_size")]
+    max_size: u64,
+}
+
+#[derive(Serialize)]
+struct Entry {
+    timestamp: f64,
+    method: String,
+    uri: String,
+    status: u16,
+    duration_ms: f64,
+    remote: Option<String>,
+}
+
+struct RequestStart(Instant);
+
+// Logs according to the current configuration, so the log can be switched
+// on, off or moved by reloading
+pub struct RequestLog {
+    sender: mpsc::Sender<Entry>,
+    receiver: Mutex<Option<mpsc::Receiver<Entry>>>,
+}
+
+impl Default for RequestLog {
+    fn default() -> RequestLog {
+        let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+        RequestLog {
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+        }
+    }
+}
+
+async fn open(path: &Path) -> std::io::Result<(File, u64)> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    let size = file.metadata().await?.len();
+    Ok((file, size))
+}
+
+async fn write_entries(
+    live: LiveConfig,
+    mut receiver: mpsc::Receiver<Entry>,
+) -> std::io::Result<()> {
+    let mut current: Option<(PathBuf, File, u64)> = None;
+    while let Some(entry) = receiver.recv().await {
+        let config = match live.current().request_log() {
+            Some(config) => config.clone(),
+            None => continue,
+        };
+        let (mut file, mut size) = match current.take() {
+            Some((path, file, size)) if path == config.path => (file, size),
+            // Moved by a reload
+            Some((_, mut file, _)) => {
+                file.flush().await?;
+                open(&config.path).await?
+            }
+            None => open(&config.path).await?,
+        };
+
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+
+        if size > 0 && size + line.len() as u64 > config.max_size {
+            file.flush().await?;
+            let mut rotated = config.path.clone().into_os_string();
+            rotated.push(".1");
+            fs::rename(&config.path, rotated).await?;
+            let (new_file, new_size) = open(&config.path).await?;
+            file = new_file;
+            size = new_size;
+        }
+
+        file.write_all(&line).await?;
+        size += line.len() as u64;
+        current = Some((config.path, file, size));
+    }
+    match current {
+        Some((_, mut file, _)) => file.flush().await,
+        None => Ok(()),
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for RequestLog {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request log",
+            kind: Kind::Liftoff | Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let receiver = self.receiver.lock().unwrap().take();
+        if let (Some(receiver), Some(live)) = (receiver, rocket.state::<LiveConfig>()) {
+            let live = live.clone();
+            rocket::tokio::spawn(async move {
+                if let Err(e) = write_entries(live, receiver).await {
+                    error!("Request log writer failed: {}", e);
+                }
+            });
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _data: &mut Data<'_>) {
+        request.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if crate::reload::current(request).map_or(true, |config| config.request_log().is_none()) {
+            return;
+        }
+        let start = request.local_cache(|| RequestStart(Instant::now()));
+        let entry = Entry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|t| t.as_secs_f64())
+                .unwrap_or_default(),
+            method: request.method().to_string(),
+            // Query strings may hold auth results, so only the path is kept
+            uri: request.uri().path().to_string(),
+            status: response.status().code,
+            duration_ms: start.0.elapsed().as_secs_f64() * 1000.0,
+            remote: request.client_ip().map(|ip| ip.to_string()),
+        };
+
+        // Never wait on the writer, dropping entries under overload is fine
+        let _ = self.sender.try_send(entry);
+    }
+}
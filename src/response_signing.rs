@@ -0,0 +1,64 @@
+use std::io::Cursor;
+
+use josekit::jws::{JwsHeader, JwsSigner};
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    Request, Response,
+};
+use tracing::warn;
+
+pub const SIGNATURE_HEADER: &str = "X-JWS-Signature";
+
+// Signs the bodies of admin API responses with admin_signing_privkey, as a
+// JWS with detached payload (RFC 7515, appendix F), so stored reports and
+// state dumps can later be shown to be unmodified
+pub struct ResponseSigner;
+
+fn detached_signature(body: &[u8], kid: &str, signer: &dyn JwsSigner) -> Option<String> {
+    let mut header = JwsHeader::new();
+    header.set_key_id(kid);
+    let token = match josekit::jws::serialize_compact(body, &header, signer) {
+        Ok(token) => token,
+        Err(e) => {
+            warn!("Failed to sign admin response: {}", e);
+            return None;
+        }
+    };
+    let mut parts = token.split('.');
+    let header = parts.next()?;
+    let signature = parts.nth(1)?;
+    Some(format!("{}..{}", header, signature))
+}
+
+#[rocket::async_trait]
+impl Fairing for ResponseSigner {
+    fn info(&self) -> Info {
+        Info {
+            name: "Admin response signing",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if !request.uri().path().starts_with("/admin/") {
+            return;
+        }
+        let (kid, signer) =
+            match crate::reload::current(request).and_then(|config| config.admin_signer()) {
+                Some(signer) => signer,
+                None => return,
+            };
+
+        let body = match response.body_mut().to_bytes().await {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Failed to read admin response for signing: {}", e);
+                return;
+            }
+        };
+        if let Some(signature) = detached_signature(&body, kid, signer) {
+            response.set_raw_header(SIGNATURE_HEADER, signature);
+        }
+        response.set_sized_body(body.len(), Cursor::new(body));
+    }
+}
@@ -0,0 +1,40 @@
+use serde::Deserialize;
+
+// Canned start_communication response for a purpose. Both urls may contain
+// the {session_id}, {purpose}, {session_query}, {server_url} and
+// {internal_url} placeholders.
+#[derive(Deserialize, Debug)]
+pub struct ResponseTemplate {
+    client_url: String,
+    attr_url: Option<String>,
+}
+
+pub struct Variables<'a> {
+    pub session_id: &'a str,
+    pub purpose: &'a str,
+    // Query string identifying the session, signed when configured
+    pub session_query: &'a str,
+    pub server_url: &'a str,
+    pub internal_url: &'a str,
+}
+
+fn render(template: &str, variables: &Variables) -> String {
+    template
+        .replace("{session_id}", variables.session_id)
+        .replace("{purpose}", variables.purpose)
+        .replace("{session_query}", variables.session_query)
+        .replace("{server_url}", variables.server_url)
+        .replace("{internal_url}", variables.internal_url)
+}
+
+impl ResponseTemplate {
+    pub fn client_url(&self, variables: &Variables) -> String {
+        render(&self.client_url, variables)
+    }
+
+    pub fn attr_url(&self, variables: &Variables) -> Option<String> {
+        self.attr_url
+            .as_ref()
+            .map(|attr_url| render(attr_url, variables))
+    }
+}
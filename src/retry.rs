@@ -0,0 +1,301 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use reqwest::{Method, RequestBuilder};
+use rocket::{fairing::AdHoc, get, serde::json::Json, Build, Rocket, State};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{access::Viewer, reload::LiveConfig, session::generate_id};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+fn default_max_attempts() -> u32 {
+    5
+}
+
+fn default_initial_backoff() -> u64 {
+    1000
+}
+
+fn default_max_backoff() -> u64 {
+    5 * 60 * 1000
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RetryConfig {
+    // Where queued and dead callbacks are kept across restarts
+    path: PathBuf,
+    // Including the first attempt
+    #[serde(default = "default_max_attempts")]
+    max_attempts: u32,
+    // Milliseconds before the first retry, doubling after each failure
+    #[serde(default = "default_initial_backoff")]
+    initial_backoff: u64,
+    #[serde(default = "default_max_backoff")]
+    max_backoff: u64,
+}
+
+impl RetryConfig {
+    fn backoff(&self, attempts: u32) -> u64 {
+        let factor = 1u64
+            .checked_shl(attempts.saturating_sub(1))
+            .unwrap_or(u64::MAX);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FailedCallback {
+    id: String,
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: String,
+    attempts: u32,
+    // Unix time in milliseconds
+    last_attempt_at: u64,
+    last_error: String,
+}
+
+impl FailedCallback {
+    fn request(&self, client: &reqwest::Client) -> RequestBuilder {
+        let method = Method::from_bytes(self.method.as_bytes()).unwrap_or(Method::POST);
+        let mut request = client.request(method, &self.url).body(self.body.clone());
+        for (name, value) in &self.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        request
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Queue {
+    pending: Vec<FailedCallback>,
+    dead_letters: Vec<FailedCallback>,
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+async fn execute(
+    request: reqwest::Request,
+    client: &reqwest::Client,
+) -> Result<(), reqwest::Error> {
+    client.execute(request).await?.error_for_status()?;
+    Ok(())
+}
+
+// Outgoing callbacks that failed, kept on disk and retried with exponential
+// backoff until they succeed or run out of attempts. Without a configured
+// path, failed callbacks are dropped as before.
+#[derive(Clone, Default)]
+pub struct RetryQueue {
+    path: Option<PathBuf>,
+    queue: Arc<Mutex<Queue>>,
+}
+
+impl RetryQueue {
+    pub fn open(config: Option<&RetryConfig>) -> RetryQueue {
+        let path = match config {
+            Some(config) => config.path.clone(),
+            None => return RetryQueue::default(),
+        };
+        let queue = match std::fs::read(&path) {
+            Ok(contents) => serde_json::from_slice(&contents).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable retry queue {}: {}", path.display(), e);
+                Queue::default()
+            }),
+            Err(_) => Queue::default(),
+        };
+        info!(
+            "Loaded {} queued and {} dead callbacks",
+            queue.pending.len(),
+            queue.dead_letters.len()
+        );
+        RetryQueue {
+            path: Some(path),
+            queue: Arc::new(Mutex::new(queue)),
+        }
+    }
+
+    // Written to a temporary file first, so a crash leaves the previous state
+    fn save(&self, queue: &Queue) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+        let temporary = path.with_extension("tmp");
+        let result = serde_json::to_vec(queue)
+            .map_err(std::io::Error::from)
+            .and_then(|contents| std::fs::write(&temporary, contents))
+            .and_then(|_| std::fs::rename(&temporary, path));
+        if let Err(e) = result {
+            warn!("Failed to save retry queue {}: {}", path.display(), e);
+        }
+    }
+
+    // Sends the request, queueing it for retries if that fails
+    pub async fn send(
+        &self,
+        client: &reqwest::Client,
+        request: RequestBuilder,
+    ) -> Result<(), reqwest::Error> {
+        let request = request.build()?;
+        let callback = self.path.as_ref().map(|_| FailedCallback {
+            id: generate_id(),
+            method: request.method().to_string(),
+            url: request.url().to_string(),
+            headers: request
+                .headers()
+                .iter()
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect(),
+            body: request
+                .body()
+                .and_then(|body| body.as_bytes())
+                .map(|body| String::from_utf8_lossy(body).into_owned())
+                .unwrap_or_default(),
+            attempts: 1,
+            last_attempt_at: 0,
+            last_error: String::new(),
+        });
+
+        let result = execute(request, client).await;
+        if let (Err(e), Some(mut callback)) = (&result, callback) {
+            info!(
+                "Queueing callback {} to {} for retry",
+                callback.id, callback.url
+            );
+            callback.last_attempt_at = unix_millis();
+            callback.last_error = e.to_string();
+            let mut queue = self.queue.lock().unwrap();
+            queue.pending.push(callback);
+            self.save(&queue);
+        }
+        result
+    }
+
+    // Moves callbacks out of attempts, also after lowering max_attempts, to
+    // the dead letters
+    fn bury_exhausted(&self, config: &RetryConfig) {
+        let mut queue = self.queue.lock().unwrap();
+        let (exhausted, pending) = std::mem::take(&mut queue.pending)
+            .into_iter()
+            .partition(|callback| callback.attempts >= config.max_attempts);
+        queue.pending = pending;
+        if !exhausted.is_empty() {
+            queue.dead_letters.extend(exhausted);
+            self.save(&queue);
+        }
+    }
+
+    fn due(&self, config: &RetryConfig) -> Vec<FailedCallback> {
+        let now = unix_millis();
+        self.queue
+            .lock()
+            .unwrap()
+            .pending
+            .iter()
+            .filter(|callback| {
+                callback
+                    .last_attempt_at
+                    .saturating_add(config.backoff(callback.attempts))
+                    <= now
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn update(
+        &self,
+        config: &RetryConfig,
+        mut callback: FailedCallback,
+        result: Result<(), String>,
+    ) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.pending.retain(|pending| pending.id != callback.id);
+        match result {
+            Ok(()) => info!(
+                "Delivered callback {} to {} after {} attempts",
+                callback.id,
+                callback.url,
+                callback.attempts + 1
+            ),
+            Err(e) => {
+                callback.attempts += 1;
+                callback.last_attempt_at = unix_millis();
+                callback.last_error = e;
+                if callback.attempts >= config.max_attempts {
+                    warn!(
+                        "Giving up on callback {} to {} after {} attempts: {}",
+                        callback.id, callback.url, callback.attempts, callback.last_error
+                    );
+                    queue.dead_letters.push(callback);
+                } else {
+                    queue.pending.push(callback);
+                }
+            }
+        }
+        self.save(&queue);
+    }
+
+    fn dead_letters(&self) -> Vec<FailedCallback> {
+        self.queue.lock().unwrap().dead_letters.clone()
+    }
+}
+
+// Retry settings other than the path follow configuration reloads
+async fn retry(live: LiveConfig, retries: RetryQueue) {
+    let client = reqwest::Client::new();
+    loop {
+        rocket::tokio::time::sleep(CHECK_INTERVAL).await;
+        let config = match live.current().retry_queue().cloned() {
+            Some(config) => config,
+            None => continue,
+        };
+        retries.bury_exhausted(&config);
+        for callback in retries.due(&config) {
+            let result = callback
+                .request(&client)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map(|_| ())
+                .map_err(|e| e.to_string());
+            retries.update(&config, callback, result);
+        }
+    }
+}
+
+pub fn attach(base: Rocket<Build>) -> Rocket<Build> {
+    base.attach(AdHoc::on_liftoff("Retry queue", |rocket| {
+        Box::pin(async move {
+            let live = match rocket.state::<LiveConfig>() {
+                Some(live) => live.clone(),
+                None => return,
+            };
+            let retries = match rocket.state::<RetryQueue>() {
+                Some(retries) if retries.path.is_some() => retries.clone(),
+                _ => return,
+            };
+            rocket::tokio::spawn(retry(live, retries));
+        })
+    }))
+}
+
+#[get("/admin/deadletters")]
+pub fn deadletters(_viewer: Viewer, retries: &State<RetryQueue>) -> Json<Vec<FailedCallback>> {
+    Json(retries.dead_letters())
+}
@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+const SAML_ASSERTION_NS: &str = "urn:oasis:names:tc:SAML:2.0:assertion";
+
+pub struct Assertion {
+    // ID of the assertion, to recognize replays
+    pub id: Option<String>,
+    // AuthnContextClassRef, carrying the level of assurance
+    pub authn_context: Option<String>,
+    pub attributes: HashMap<String, String>,
+}
+
+// Note that no signature validation is done on the assertion, callers need
+// to check that unverified assertions are allowed
+pub fn parse(response: &str) -> Result<Assertion, roxmltree::Error> {
+    let document = roxmltree::Document::parse(response)?;
+
+    let id = document
+        .descendants()
+        .find(|node| node.has_tag_name((SAML_ASSERTION_NS, "Assertion")))
+        .and_then(|assertion| assertion.attribute("ID"))
+        .map(|id| id.to_string());
+    let authn_context = document
+        .descendants()
+        .find(|node| node.has_tag_name((SAML_ASSERTION_NS, "AuthnContextClassRef")))
+        .and_then(|node| node.text())
+        .map(|class| class.trim().to_string());
+
+    let mut attributes = HashMap::new();
+    for attribute in document
+        .descendants()
+        .filter(|node| node.has_tag_name((SAML_ASSERTION_NS, "Attribute")))
+    {
+        let name = match attribute.attribute("Name") {
+            Some(name) => name,
+            None => continue,
+        };
+        let values: Vec<&str> = attribute
+            .children()
+            .filter(|node| node.has_tag_name((SAML_ASSERTION_NS, "AttributeValue")))
+            .filter_map(|node| node.text())
+            .map(|value| value.trim())
+            .collect();
+        attributes.insert(name.to_string(), values.join(","));
+    }
+
+    Ok(Assertion {
+        id,
+        authn_context,
+        attributes,
+    })
+}
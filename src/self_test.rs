@@ -0,0 +1,77 @@
+use std::{error::Error as StdError, fmt::Display};
+
+use id_contact_jwt::{decrypt_and_verify_auth_result, sign_and_encrypt_auth_result};
+use id_contact_proto::AuthResult;
+use josekit::{jwe::RSA_OAEP, jws::RS256, JoseError};
+
+#[derive(Debug)]
+pub enum Error {
+    Jose(JoseError),
+    Jwt(id_contact_jwt::Error),
+    Json(serde_json::Error),
+    Mismatch,
+}
+
+impl From<JoseError> for Error {
+    fn from(e: JoseError) -> Error {
+        Error::Jose(e)
+    }
+}
+
+impl From<id_contact_jwt::Error> for Error {
+    fn from(e: id_contact_jwt::Error) -> Error {
+        Error::Jwt(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::Json(e)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Jose(e) => e.fmt(f),
+            Error::Jwt(e) => e.fmt(f),
+            Error::Json(e) => e.fmt(f),
+            Error::Mismatch => f.write_str("Decrypted auth result does not match original"),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Jose(e) => Some(e),
+            Error::Jwt(e) => Some(e),
+            Error::Json(e) => Some(e),
+            Error::Mismatch => None,
+        }
+    }
+}
+
+// Round trip an auth result through the full crypto stack using ephemeral
+// keys, to catch broken crypto backends before any real traffic arrives
+pub fn run() -> Result<(), Error> {
+    let sign_key = RS256.generate_key_pair(2048)?;
+    let enc_key = RSA_OAEP.generate_key_pair(2048)?;
+    let signer = RS256.signer_from_der(sign_key.to_der_private_key())?;
+    let verifier = RS256.verifier_from_der(sign_key.to_der_public_key())?;
+    let encrypter = RSA_OAEP.encrypter_from_der(enc_key.to_der_public_key())?;
+    let decrypter = RSA_OAEP.decrypter_from_der(enc_key.to_der_private_key())?;
+
+    let original: AuthResult = serde_json::from_value(serde_json::json!({
+        "status": "succes",
+        "attributes": { "email": "self-test@example.com" },
+    }))?;
+    let jwe = sign_and_encrypt_auth_result(&original, &signer, &encrypter)?;
+    let decoded = decrypt_and_verify_auth_result(&jwe, &verifier, &decrypter)?;
+
+    if serde_json::to_value(&original)? != serde_json::to_value(&decoded)? {
+        return Err(Error::Mismatch);
+    }
+
+    Ok(())
+}
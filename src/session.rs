@@ -0,0 +1,608 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet, HashMap},
+    convert::Infallible,
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use id_contact_proto::AuthResult;
+use rand::Rng;
+use rocket::{
+    get, put,
+    request::{FromRequest, Outcome},
+    serde::json::Json,
+    Request, State,
+};
+use serde::Serialize;
+use serde_json::json;
+use tracing::info;
+
+#[cfg(feature = "outbox")]
+use crate::outbox::Outbox;
+use crate::{
+    access::{Operator, Viewer},
+    assurance::AssuranceLevel,
+    cases::CaseStore,
+    clock::Clock,
+    config::DuplicateDeliveryPolicy,
+    events::Events,
+    keys, Error,
+};
+
+// Which path delivered an attribute, to debug sessions receiving auth
+// results both inline and through attr_url
+#[derive(Serialize, Debug, Clone)]
+pub struct Provenance {
+    source: &'static str,
+    received_at: SystemTime,
+    kid: Option<String>,
+}
+
+// Path an auth result is delivered through
+pub struct Source {
+    name: &'static str,
+    kid: Option<String>,
+}
+
+impl Source {
+    // The kid is read from the token the auth result was decoded from
+    pub fn new(name: &'static str, token: Option<&str>) -> Source {
+        Source {
+            name,
+            kid: token.and_then(keys::jwe_key_id),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Session {
+    id: String,
+    purpose: String,
+    created_at: SystemTime,
+    auth_result: Option<AuthResult>,
+    auth_result_received_at: Option<SystemTime>,
+    case_id: Option<String>,
+    // Why the auth_result sent along when starting was rejected, if it was
+    invalid_auth_result: Option<String>,
+    clock: Clock,
+    assurance_level: Option<AssuranceLevel>,
+    provenance: BTreeMap<String, Provenance>,
+    attributes_wiped: bool,
+    // Set by the starter, to keep overlapping test campaigns apart
+    tags: BTreeSet<String>,
+}
+
+impl Session {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn purpose(&self) -> &str {
+        &self.purpose
+    }
+
+    pub fn created_at(&self) -> SystemTime {
+        self.created_at
+    }
+
+    pub fn auth_result(&self) -> Option<&AuthResult> {
+        self.auth_result.as_ref()
+    }
+
+    pub fn auth_result_received_at(&self) -> Option<SystemTime> {
+        self.auth_result_received_at
+    }
+
+    // Time since the session started
+    pub fn age(&self) -> Duration {
+        self.clock
+            .now()
+            .duration_since(self.created_at)
+            .unwrap_or_default()
+    }
+
+    pub fn status(&self) -> &'static str {
+        match &self.auth_result {
+            _ if self.invalid_auth_result.is_some() => "invalid",
+            Some(auth_result) if crate::is_failed(auth_result) => "failed",
+            Some(_) => "success",
+            None => "pending",
+        }
+    }
+
+    pub fn case_id(&self) -> Option<&str> {
+        self.case_id.as_deref()
+    }
+
+    pub fn invalid_auth_result(&self) -> Option<&str> {
+        self.invalid_auth_result.as_deref()
+    }
+
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    pub fn assurance_level(&self) -> Option<AssuranceLevel> {
+        self.assurance_level
+    }
+
+    // Time since the auth result was received
+    fn attributes_age(&self) -> Option<Duration> {
+        let received_at = self.auth_result_received_at?;
+        Some(
+            self.clock
+                .now()
+                .duration_since(received_at)
+                .unwrap_or_default(),
+        )
+    }
+
+    pub fn provenance(&self) -> &BTreeMap<String, Provenance> {
+        &self.provenance
+    }
+
+    pub fn tags(&self) -> &BTreeSet<String> {
+        &self.tags
+    }
+
+    // Listings filter on an optional tag, without one every session matches
+    pub fn tagged(&self, tag: Option<&str>) -> bool {
+        tag.map_or(true, |tag| self.tags.contains(tag))
+    }
+
+    // Attributes are tracked as held, so a replaced auth result drops the
+    // provenance of the attributes it no longer has
+    fn track(&mut self, source: &Source, received_at: SystemTime) {
+        let attributes = self
+            .auth_result
+            .as_ref()
+            .and_then(|auth_result| auth_result.attributes.as_ref());
+        self.provenance = attributes
+            .into_iter()
+            .flat_map(|attributes| attributes.keys())
+            .map(|name| {
+                let provenance = Provenance {
+                    source: source.name,
+                    received_at,
+                    kid: source.kid.clone(),
+                };
+                (name.clone(), provenance)
+            })
+            .collect();
+    }
+}
+
+const SHARDS: usize = 16;
+
+type Shard = Mutex<HashMap<String, Session>>;
+
+// Sessions spread over separately locked shards by id, so that requests for
+// different sessions rarely wait on each other under load
+#[derive(Default)]
+struct Shards([Shard; SHARDS]);
+
+impl Shards {
+    fn shard(&self, id: &str) -> &Shard {
+        let mut hasher = DefaultHasher::new();
+        id.hash(&mut hasher);
+        &self.0[hasher.finish() as usize % SHARDS]
+    }
+
+    fn all(&self) -> impl Iterator<Item = &Shard> {
+        self.0.iter()
+    }
+}
+
+// Cheaply cloneable, so that non-http transports can share the store
+#[derive(Clone, Default)]
+pub struct SessionStore {
+    sessions: Arc<Shards>,
+    cases: Option<CaseStore>,
+    events: Events,
+    // Opaque handles given out in client urls, with the session they stand for
+    handles: Arc<Mutex<HashMap<String, (String, SystemTime)>>>,
+    #[cfg(feature = "outbox")]
+    outbox: Option<Arc<Outbox>>,
+}
+
+// Redelivery of the same auth result isn't a conflict
+fn differs(previous: &AuthResult, auth_result: &AuthResult) -> bool {
+    serde_json::to_value(previous).ok() != serde_json::to_value(auth_result).ok()
+}
+
+// Snapshots of changed sessions, to be persisted to the outbox
+type Changes = Vec<(Session, &'static str)>;
+
+pub fn generate_id() -> String {
+    hex::encode(rand::thread_rng().gen::<[u8; 16]>())
+}
+
+impl SessionStore {
+    #[cfg(feature = "outbox")]
+    pub fn with_outbox(mut self, outbox: Outbox) -> SessionStore {
+        self.outbox = Some(Arc::new(outbox));
+        self
+    }
+
+    pub fn with_cases(mut self, cases: CaseStore) -> SessionStore {
+        self.cases = Some(cases);
+        self
+    }
+
+    pub fn cases(&self) -> Option<&CaseStore> {
+        self.cases.as_ref()
+    }
+
+    pub fn events(&self) -> &Events {
+        &self.events
+    }
+
+    #[cfg(feature = "outbox")]
+    pub fn outbox(&self) -> Option<Arc<Outbox>> {
+        self.outbox.clone()
+    }
+
+    // Links the session to a case, noting the changes for the outbox
+    fn link_case(&self, session: &mut Session, changes: &mut Changes) {
+        let linked = match (&self.cases, &session.auth_result) {
+            (Some(cases), Some(auth_result)) => cases.link(&session.id, auth_result),
+            _ => None,
+        };
+        if let Some((case_id, created)) = linked {
+            if created {
+                info!("Opened case {}", case_id);
+                changes.push((session.clone(), "case_opened"));
+                self.events
+                    .emit("case_opened", json!({ "case_id": case_id }));
+            }
+            info!("Linked session {} to case {}", session.id, case_id);
+            session.case_id = Some(case_id.clone());
+            changes.push((session.clone(), "case_session_linked"));
+            self.events.emit(
+                "case_session_linked",
+                json!({ "session_id": session.id, "case_id": case_id }),
+            );
+        }
+    }
+
+    // Called without the sessions lock held, so a slow disk doesn't block
+    // every other request
+    #[cfg(feature = "outbox")]
+    fn record(&self, changes: Changes) {
+        if let Some(outbox) = &self.outbox {
+            for (session, kind) in changes {
+                if let Err(e) = outbox.record(&session, kind) {
+                    tracing::warn!("Failed to persist session {}: {}", session.id, e);
+                }
+            }
+        }
+    }
+
+    #[cfg(not(feature = "outbox"))]
+    fn record(&self, _changes: Changes) {}
+
+    // Hands out a short-lived handle standing in for the session, so the
+    // client url needn't carry the session id or the auth result
+    pub fn issue_handle(&self, id: &str, lifetime: u64, clock: &Clock) -> String {
+        let now = clock.now();
+        let handle = generate_id();
+        let mut handles = self.handles.lock().unwrap();
+        handles.retain(|_, (_, expires_at)| *expires_at > now);
+        handles.insert(
+            handle.clone(),
+            (
+                id.to_string(),
+                now.checked_add(Duration::from_secs(lifetime))
+                    .unwrap_or(now),
+            ),
+        );
+        handle
+    }
+
+    pub fn resolve_handle(&self, handle: &str, clock: &Clock) -> Option<String> {
+        let handles = self.handles.lock().unwrap();
+        match handles.get(handle) {
+            Some((id, expires_at)) if *expires_at > clock.now() => Some(id.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn create(
+        &self,
+        purpose: &str,
+        auth_result: Option<AuthResult>,
+        source: Source,
+        clock: Clock,
+    ) -> String {
+        let id = generate_id();
+        let created_at = clock.now();
+        let mut session = Session {
+            id: id.clone(),
+            purpose: purpose.to_string(),
+            created_at,
+            auth_result_received_at: auth_result.as_ref().map(|_| created_at),
+            auth_result,
+            case_id: None,
+            invalid_auth_result: None,
+            clock,
+            assurance_level: None,
+            provenance: BTreeMap::new(),
+            attributes_wiped: false,
+            tags: BTreeSet::new(),
+        };
+        session.track(&source, created_at);
+        let mut changes = vec![(session.clone(), "session_started")];
+        self.events.emit(
+            "session_started",
+            json!({ "session_id": id, "purpose": purpose }),
+        );
+        if session.auth_result.as_ref().map_or(false, crate::is_failed) {
+            changes.push((session.clone(), "auth_failed"));
+            self.events.emit("auth_failed", json!({ "session_id": id }));
+        }
+        self.link_case(&mut session, &mut changes);
+        self.sessions
+            .shard(&id)
+            .lock()
+            .unwrap()
+            .insert(id.clone(), session);
+        self.record(changes);
+        id
+    }
+
+    pub fn list(&self) -> Vec<Session> {
+        let mut sessions: Vec<Session> = self
+            .sessions
+            .all()
+            .flat_map(|shard| {
+                let shard = shard.lock().unwrap();
+                shard.values().cloned().collect::<Vec<_>>()
+            })
+            .collect();
+        sessions.sort_by_key(|session| session.created_at);
+        sessions
+    }
+
+    pub fn remove_expired(&self, timeout: Duration) -> Vec<Session> {
+        let mut removed = Vec::new();
+        for shard in self.sessions.all() {
+            let mut sessions = shard.lock().unwrap();
+            let expired: Vec<String> = sessions
+                .values()
+                .filter(|session| session.age() >= timeout)
+                .map(|session| session.id.clone())
+                .collect();
+            removed.extend(expired.iter().filter_map(|id| sessions.remove(id)));
+        }
+        removed
+    }
+
+    pub fn get(&self, id: &str) -> Option<Session> {
+        self.sessions.shard(id).lock().unwrap().get(id).cloned()
+    }
+
+    // Wipes attribute values received longer than lifetime ago, keeping the
+    // rest of the session. Returns the ids of the sessions wiped.
+    pub fn wipe_attributes(&self, lifetime: Duration) -> Vec<String> {
+        let mut changes = Vec::new();
+        let mut wiped = Vec::new();
+        for shard in self.sessions.all() {
+            let mut sessions = shard.lock().unwrap();
+            for session in sessions.values_mut() {
+                let expired = session
+                    .attributes_age()
+                    .map_or(false, |age| age >= lifetime);
+                let attributes = session
+                    .auth_result
+                    .as_mut()
+                    .and_then(|auth_result| auth_result.attributes.as_mut());
+                match attributes {
+                    Some(attributes) if expired && !attributes.is_empty() => attributes.clear(),
+                    _ => continue,
+                }
+                session.attributes_wiped = true;
+                self.events
+                    .emit("attributes_wiped", json!({ "session_id": session.id }));
+                changes.push((session.clone(), "attributes_wiped"));
+                wiped.push(session.id.clone());
+            }
+        }
+        self.record(changes);
+        wiped
+    }
+
+    pub fn flag_invalid_auth_result(&self, id: &str, reason: String) {
+        if let Some(session) = self.sessions.shard(id).lock().unwrap().get_mut(id) {
+            self.events.emit(
+                "invalid_auth_result",
+                json!({ "session_id": id, "reason": reason }),
+            );
+            session.invalid_auth_result = Some(reason);
+        }
+    }
+
+    // Returns false if there is no session with the given id
+    pub fn set_tags(&self, id: &str, tags: BTreeSet<String>) -> bool {
+        match self.sessions.shard(id).lock().unwrap().get_mut(id) {
+            Some(session) => {
+                session.tags = tags;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_assurance_level(&self, id: &str, level: AssuranceLevel) {
+        if let Some(session) = self.sessions.shard(id).lock().unwrap().get_mut(id) {
+            session.assurance_level = Some(level);
+        }
+    }
+
+    // Returns false if there is no session with the given id
+    fn store_auth_result(
+        &self,
+        id: &str,
+        auth_result: AuthResult,
+        policy: DuplicateDeliveryPolicy,
+        source: Source,
+    ) -> Result<bool, Error> {
+        let failed = crate::is_failed(&auth_result);
+        let mut changes = Vec::new();
+        let known = match self.sessions.shard(id).lock().unwrap().get_mut(id) {
+            Some(session) => {
+                if let Some(previous) = &session.auth_result {
+                    if differs(previous, &auth_result) {
+                        info!("Session {} received a different auth result", id);
+                        self.events.emit(
+                            "auth_result_conflict",
+                            json!({ "session_id": id, "policy": policy }),
+                        );
+                        match policy {
+                            DuplicateDeliveryPolicy::FirstWins => return Ok(true),
+                            DuplicateDeliveryPolicy::Reject => {
+                                return Err(Error::ConflictingAuthResult)
+                            }
+                            DuplicateDeliveryPolicy::LastWins => {}
+                        }
+                    }
+                }
+                session.auth_result = Some(auth_result);
+                // A later auth result replaces the invalid one
+                session.invalid_auth_result = None;
+                let received_at = session.clock.now();
+                session.auth_result_received_at = Some(received_at);
+                session.attributes_wiped = false;
+                session.track(&source, received_at);
+                changes.push((session.clone(), "auth_result_received"));
+                if failed {
+                    changes.push((session.clone(), "auth_failed"));
+                }
+                self.link_case(session, &mut changes);
+                true
+            }
+            None => false,
+        };
+        self.record(changes);
+        Ok(known)
+    }
+
+    // Returns false if there is no session with the given id
+    pub fn set_auth_result(
+        &self,
+        id: &str,
+        auth_result: AuthResult,
+        policy: DuplicateDeliveryPolicy,
+        source: Source,
+    ) -> Result<bool, Error> {
+        let failed = crate::is_failed(&auth_result);
+        let known = self.store_auth_result(id, auth_result, policy, source)?;
+        if known {
+            self.events.emit(
+                "auth_result_received",
+                json!({ "session_id": id, "failed": failed }),
+            );
+            if failed {
+                self.events.emit("auth_failed", json!({ "session_id": id }));
+            }
+        }
+        Ok(known)
+    }
+
+    // An auth result delivered out of band under request_id, linked to the
+    // session it names if that exists. Returns whether it was linked.
+    pub fn deliver(
+        &self,
+        request_id: &str,
+        session: Option<&str>,
+        auth_result: AuthResult,
+        policy: DuplicateDeliveryPolicy,
+        source: Source,
+    ) -> Result<bool, Error> {
+        let failed = crate::is_failed(&auth_result);
+        let linked = match session {
+            Some(id) => self.store_auth_result(id, auth_result, policy, source)?,
+            None => false,
+        };
+        let session = session.filter(|_| linked);
+        self.events.emit(
+            "auth_result_received",
+            json!({ "request_id": request_id, "session_id": session, "failed": failed }),
+        );
+        if failed {
+            self.events.emit(
+                "auth_failed",
+                json!({ "request_id": request_id, "session_id": session }),
+            );
+        }
+        Ok(linked)
+    }
+}
+
+#[derive(Serialize)]
+pub struct SessionStatus {
+    session_id: String,
+    purpose: String,
+    status: &'static str,
+    attributes_received: bool,
+    auth_result_received_at: Option<SystemTime>,
+    // Per attribute, which path delivered it
+    provenance: BTreeMap<String, Provenance>,
+    // Set once attribute values outlived attribute_lifetime
+    attributes_wiped: bool,
+    tags: BTreeSet<String>,
+}
+
+// Lets tests check whether attributes delivered out of band, through
+// attr_url, reached the session
+#[get("/internal/sessions/<id>/status")]
+pub fn status(
+    _viewer: Viewer,
+    id: String,
+    sessions: &State<SessionStore>,
+) -> Option<Json<SessionStatus>> {
+    let session = sessions.get(&id)?;
+    Some(Json(SessionStatus {
+        status: session.status(),
+        attributes_received: session.auth_result.is_some(),
+        auth_result_received_at: session.auth_result_received_at,
+        provenance: session.provenance,
+        attributes_wiped: session.attributes_wiped,
+        tags: session.tags,
+        session_id: session.id,
+        purpose: session.purpose,
+    }))
+}
+
+// Tags for the session a request starts, as a comma separated list
+pub const SESSION_TAGS_HEADER: &str = "X-Session-Tags";
+
+pub struct SessionTags(pub BTreeSet<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SessionTags {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let tags = request
+            .headers()
+            .get(SESSION_TAGS_HEADER)
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(|tag| tag.to_string())
+            .collect();
+        Outcome::Success(SessionTags(tags))
+    }
+}
+
+// Replaces the tags of a session, for tagging sessions started elsewhere
+#[put("/admin/sessions/<id>/tags", data = "<tags>")]
+pub fn set_tags(
+    _operator: Operator,
+    id: String,
+    tags: Json<BTreeSet<String>>,
+    sessions: &State<SessionStore>,
+) -> Option<()> {
+    sessions.set_tags(&id, tags.into_inner()).then(|| ())
+}
@@ -0,0 +1,90 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    Request, Response,
+};
+use serde::Serialize;
+
+// Number of recent errors kept for display
+const ERROR_HISTORY: usize = 50;
+
+#[derive(Default, Clone, Copy, Serialize)]
+pub struct RouteCounts {
+    pub total: u64,
+    pub client_errors: u64,
+    pub server_errors: u64,
+}
+
+#[derive(Clone)]
+pub struct RecordedError {
+    pub at: SystemTime,
+    pub method: String,
+    pub uri: String,
+    pub status: u16,
+}
+
+#[derive(Default)]
+struct Inner {
+    routes: BTreeMap<String, RouteCounts>,
+    errors: VecDeque<RecordedError>,
+}
+
+// Shared between the fairing collecting the numbers and managed state
+#[derive(Clone, Default)]
+pub struct RouteStats {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl RouteStats {
+    pub fn routes(&self) -> BTreeMap<String, RouteCounts> {
+        self.inner.lock().unwrap().routes.clone()
+    }
+
+    pub fn recent_errors(&self) -> Vec<RecordedError> {
+        self.inner.lock().unwrap().errors.iter().cloned().collect()
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for RouteStats {
+    fn info(&self) -> Info {
+        Info {
+            name: "Route statistics",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let route = match request.route() {
+            Some(route) => format!("{} {}", route.method, route.uri),
+            None => "unmatched".to_string(),
+        };
+        let status = response.status().code;
+
+        let mut inner = self.inner.lock().unwrap();
+        let counts = inner.routes.entry(route).or_default();
+        counts.total += 1;
+        match status {
+            400..=499 => counts.client_errors += 1,
+            500..=599 => counts.server_errors += 1,
+            _ => {}
+        }
+
+        if status >= 400 {
+            if inner.errors.len() >= ERROR_HISTORY {
+                inner.errors.pop_front();
+            }
+            inner.errors.push_back(RecordedError {
+                at: SystemTime::now(),
+                method: request.method().to_string(),
+                uri: request.uri().to_string(),
+                status,
+            });
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use rocket::{form::Form, get, post, FromForm, State};
+use rocket_dyn_templates::Template;
+use serde::Serialize;
+use serde_json::json;
+use tracing::info;
+
+use crate::{
+    assurance::AssuranceLevel,
+    check_required_attributes,
+    clock::Clock,
+    config::Config,
+    deadline::Deadline,
+    decode_auth_result,
+    formatting::{FormatterRegistry, Locale},
+    is_failed,
+    jti::JtiCache,
+    logging::Redacted,
+    mirror::Mirror,
+    recording::Recording,
+    session::{Session, SessionStore, Source},
+    url_signing, Error,
+};
+
+#[derive(Serialize)]
+struct Attribute<'a> {
+    name: &'a str,
+    value: &'a str,
+    // For display in the negotiated locale, when it differs from the value
+    formatted: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CaseContext {
+    id: String,
+    sessions: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct SessionContext<'a> {
+    id: &'a str,
+    purpose: &'a str,
+    status: &'static str,
+    attributes: Vec<Attribute<'a>>,
+    case: Option<CaseContext>,
+    invalid_auth_result: Option<&'a str>,
+    clock_skew: i64,
+    assurance_level: Option<AssuranceLevel>,
+}
+
+impl<'a> SessionContext<'a> {
+    fn new(
+        session: &'a Session,
+        sessions: &SessionStore,
+        formatters: &FormatterRegistry,
+        locale: Locale,
+    ) -> SessionContext<'a> {
+        let status = session.status();
+        let mut attributes: Vec<_> = session
+            .auth_result()
+            .and_then(|auth_result| auth_result.attributes.as_ref())
+            .into_iter()
+            .flatten()
+            .map(|(name, value)| Attribute {
+                name,
+                value,
+                formatted: formatters.format(name, value, locale),
+            })
+            .collect();
+        attributes.sort_by_key(|attribute| attribute.name);
+        let case = session
+            .case_id()
+            .and_then(|case_id| sessions.cases()?.get(case_id))
+            .map(|case| CaseContext {
+                id: case.id().to_string(),
+                sessions: case.sessions().to_vec(),
+            });
+
+        SessionContext {
+            id: session.id(),
+            purpose: session.purpose(),
+            status,
+            attributes,
+            case,
+            invalid_auth_result: session.invalid_auth_result(),
+            clock_skew: session.clock().skew(),
+            assurance_level: session.assurance_level(),
+        }
+    }
+}
+
+#[derive(FromForm, Serialize)]
+pub struct UiParams {
+    result: Option<String>,
+    session: Option<String>,
+    handle: Option<String>,
+    expires: Option<u64>,
+    signature: Option<String>,
+}
+
+impl UiParams {
+    // Url encoded, as posted by the bridge page
+    fn form_body(&self) -> Vec<u8> {
+        let mut url = reqwest::Url::parse("http://localhost/").unwrap();
+        {
+            let mut pairs = url.query_pairs_mut();
+            let expires = self.expires.map(|expires| expires.to_string());
+            for (name, value) in [
+                ("result", &self.result),
+                ("session", &self.session),
+                ("handle", &self.handle),
+                ("expires", &expires),
+                ("signature", &self.signature),
+            ] {
+                if let Some(value) = value {
+                    pairs.append_pair(name, value);
+                }
+            }
+        }
+        url.query().unwrap_or_default().as_bytes().to_vec()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn render(
+    params: UiParams,
+    config: &Config,
+    sessions: &SessionStore,
+    jtis: &JtiCache,
+    deadline: Deadline,
+    recording: Recording<'_>,
+    clock: Clock,
+    locale: Locale,
+) -> Result<Template, Error> {
+    let UiParams {
+        result,
+        session,
+        handle,
+        expires,
+        signature,
+    } = params;
+
+    // A handle stands in for the session, and is unguessable and short-lived
+    // itself, so it needs no signature
+    let session = match handle {
+        Some(handle) => Some(
+            sessions
+                .resolve_handle(&handle, &clock)
+                .ok_or(Error::LinkExpired)?,
+        ),
+        None => {
+            if let Some(signing) = config.client_url_signing() {
+                // Links expire according to the clock of the session they were made for
+                let clock = session
+                    .as_deref()
+                    .and_then(|id| sessions.get(id))
+                    .map(|session| *session.clock())
+                    .unwrap_or(clock);
+                url_signing::verify(
+                    signing,
+                    session.as_deref(),
+                    expires,
+                    signature.as_deref(),
+                    &clock,
+                )?;
+            }
+            session
+        }
+    };
+
+    let mut failed = false;
+    if let Some(result) = result {
+        info!("Received inline authentication results {:?}", &result);
+
+        deadline.check()?;
+        let session_result = decode_auth_result(&result, config, sessions.events(), &clock)?;
+        jtis.check(&result, config)?;
+        deadline.check()?;
+        info!("Decoded: {}", Redacted(&session_result));
+        recording.auth_result(&session_result);
+
+        failed = is_failed(&session_result);
+        if let Some(session) = session.as_deref().and_then(|id| sessions.get(id)) {
+            let assurance_level = match config.assurance() {
+                Some(assurance) => {
+                    let level = assurance.level(&result, config);
+                    assurance.check(session.purpose(), level)?;
+                    level
+                }
+                None => None,
+            };
+            sessions.set_auth_result(
+                session.id(),
+                session_result.clone(),
+                config.on_duplicate_delivery(),
+                Source::new("ui", Some(result.as_str())),
+            )?;
+            if let Some(level) = assurance_level {
+                sessions.set_assurance_level(session.id(), level);
+            }
+            check_required_attributes(config, sessions, session.id(), &session_result)?;
+        }
+
+        if failed {
+            info!("Authentication failed for inline authentication result");
+        }
+    }
+
+    let session = session.and_then(|id| sessions.get(&id));
+    let formatters = FormatterRegistry::new(config.attribute_formats());
+    Ok(Template::render(
+        "ui",
+        json!({
+            "failed": failed,
+            "session": session
+                .as_ref()
+                .map(|session| SessionContext::new(session, sessions, &formatters, locale)),
+        }),
+    ))
+}
+
+#[allow(clippy::too_many_arguments)]
+#[get("/ui?<params..>")]
+pub fn ui(
+    params: UiParams,
+    config: &Config,
+    sessions: &State<SessionStore>,
+    jtis: &State<JtiCache>,
+    mirror: Mirror<'_>,
+    deadline: Deadline,
+    recording: Recording<'_>,
+    clock: Clock,
+    locale: Locale,
+) -> Result<Template, Error> {
+    mirror.forward(Vec::new());
+
+    // Move the result out of the url, and with that out of browser history
+    // and access logs, by having the browser post it instead
+    if config.ui_post_bridge() && params.result.is_some() {
+        info!("Bridging inline authentication result to POST");
+        return Ok(Template::render("ui_bridge", &params));
+    }
+
+    render(
+        params, config, sessions, jtis, deadline, recording, clock, locale,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+#[post("/ui", data = "<params>")]
+pub fn ui_post(
+    params: Form<UiParams>,
+    config: &Config,
+    sessions: &State<SessionStore>,
+    jtis: &State<JtiCache>,
+    mirror: Mirror<'_>,
+    deadline: Deadline,
+    recording: Recording<'_>,
+    clock: Clock,
+    locale: Locale,
+) -> Result<Template, Error> {
+    let body = params.form_body();
+    recording.body(&body);
+    mirror.forward(body);
+
+    render(
+        params.into_inner(),
+        config,
+        sessions,
+        jtis,
+        deadline,
+        recording,
+        clock,
+        locale,
+    )
+}
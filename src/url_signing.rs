@@ -0,0 +1,56 @@
+use hmac::{Hmac, Mac, NewMac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+use crate::{clock::Clock, Error};
+
+fn default_validity() -> u64 {
+    3600
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UrlSigningConfig {
+    secret: String,
+    // Seconds a generated client_url remains valid
+    #[serde(default = "default_validity")]
+    validity: u64,
+}
+
+fn mac(config: &UrlSigningConfig, session: &str, expires: u64) -> Hmac<Sha256> {
+    // Hmac accepts keys of any length, so this can't fail
+    let mut mac = Hmac::<Sha256>::new_from_slice(config.secret.as_bytes()).unwrap();
+    mac.update(format!("session={}&expires={}", session, expires).as_bytes());
+    mac
+}
+
+// Query string for a signed link to the given session
+pub fn signed_query(config: &UrlSigningConfig, session: &str, clock: &Clock) -> String {
+    let expires = clock.unix_now() + config.validity;
+    let signature = hex::encode(mac(config, session, expires).finalize().into_bytes());
+    format!(
+        "session={}&expires={}&signature={}",
+        session, expires, signature
+    )
+}
+
+pub fn verify(
+    config: &UrlSigningConfig,
+    session: Option<&str>,
+    expires: Option<u64>,
+    signature: Option<&str>,
+    clock: &Clock,
+) -> Result<(), Error> {
+    let (session, expires, signature) = match (session, expires, signature) {
+        (Some(session), Some(expires), Some(signature)) => (session, expires, signature),
+        _ => return Err(Error::InvalidSignature),
+    };
+    let signature = hex::decode(signature).map_err(|_| Error::InvalidSignature)?;
+    mac(config, session, expires)
+        .verify(&signature)
+        .map_err(|_| Error::InvalidSignature)?;
+
+    if expires < clock.unix_now() {
+        return Err(Error::LinkExpired);
+    }
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use std::convert::Infallible;
+
+use hmac::{Hmac, Mac, NewMac};
+use rocket::{
+    get, post,
+    request::{FromRequest, Outcome},
+    serde::json::Json,
+    Request, State,
+};
+use serde::Serialize;
+use sha2::Sha256;
+use tracing::info;
+
+use crate::{
+    access::Viewer,
+    config::{Config, WebhookSinkConfig},
+    recording::{Recorder, Recording},
+    Error,
+};
+
+const SINK_PATH: &str = "/admin/webhook-sink";
+
+// Received webhooks are kept with the other recorded requests, this is the
+// view on them for assertions
+#[derive(Serialize)]
+pub struct ReceivedWebhook {
+    received_at: u64,
+    verified_by: &'static str,
+    body: String,
+}
+
+pub struct Signature(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Signature {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let header = crate::reload::current(request)
+            .and_then(|config| config.webhook_sink())
+            .map(|sink| sink.signature_header())
+            .and_then(|header| request.headers().get_one(header));
+        Outcome::Success(Signature(header.map(|value| value.to_string())))
+    }
+}
+
+fn verify_hmac(secret: &str, signature: &str, body: &[u8]) -> bool {
+    let signature = match hex::decode(signature.trim_start_matches("sha256=")) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+    let mut mac = match Hmac::<Sha256>::new_from_slice(secret.as_bytes()) {
+        Ok(mac) => mac,
+        Err(_) => return false,
+    };
+    mac.update(body);
+    mac.verify(&signature).is_ok()
+}
+
+fn verify_jws(validator: &dyn josekit::jws::JwsVerifier, signature: &str, body: &[u8]) -> bool {
+    match josekit::jws::deserialize_compact(signature, validator) {
+        Ok((payload, _)) => payload == body,
+        Err(_) => false,
+    }
+}
+
+fn verify(
+    config: &WebhookSinkConfig,
+    signature: Option<&str>,
+    body: &[u8],
+) -> Result<&'static str, Error> {
+    if config.hmac_secret().is_none() && config.jws_validator().is_none() {
+        return Ok("none");
+    }
+
+    let signature = signature.ok_or(Error::InvalidSignature)?;
+    if let Some(secret) = config.hmac_secret() {
+        if verify_hmac(secret, signature, body) {
+            return Ok("hmac");
+        }
+    }
+    if let Some(validator) = config.jws_validator() {
+        if verify_jws(validator, signature, body) {
+            return Ok("jws");
+        }
+    }
+
+    Err(Error::InvalidSignature)
+}
+
+#[post("/admin/webhook-sink", data = "<body>")]
+pub fn receive(
+    body: Vec<u8>,
+    signature: Signature,
+    config: &Config,
+    recording: Recording<'_>,
+) -> Result<(), Error> {
+    let sink_config = config
+        .webhook_sink()
+        .ok_or(Error::WebhookSinkNotConfigured)?;
+    let verified_by = verify(sink_config, signature.0.as_deref(), &body)?;
+    let body = String::from_utf8(body).map_err(|e| e.utf8_error())?;
+    info!("Received webhook ({}) {:?}", verified_by, body);
+    recording.body(body.as_bytes());
+    recording.verified_by(verified_by);
+    Ok(())
+}
+
+#[get("/admin/webhook-sink")]
+pub fn list(_viewer: Viewer, recorder: &State<Recorder>) -> Json<Vec<ReceivedWebhook>> {
+    let received = recorder
+        .successful("POST", SINK_PATH)
+        .into_iter()
+        .map(|request| ReceivedWebhook {
+            received_at: request.received_at(),
+            verified_by: request.verified_by().unwrap_or("none"),
+            body: request.body().to_string(),
+        })
+        .collect();
+    Json(received)
+}
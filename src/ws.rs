@@ -0,0 +1,109 @@
+use std::net::SocketAddr;
+
+use rocket::{
+    fairing::AdHoc,
+    futures::SinkExt,
+    tokio::{
+        net::{TcpListener, TcpStream},
+        sync::broadcast::error::RecvError,
+    },
+    Build, Rocket,
+};
+use tokio_tungstenite::tungstenite::{
+    handshake::server::{ErrorResponse, Request, Response},
+    http::StatusCode,
+    Message,
+};
+use tracing::{error, info};
+
+use crate::{
+    access::{self, Role},
+    config::Config,
+    events::Events,
+    reload::LiveConfig,
+};
+
+const EVENTS_PATH: &str = "/internal/events";
+
+fn reject(status: StatusCode) -> ErrorResponse {
+    let mut response = ErrorResponse::new(None);
+    *response.status_mut() = status;
+    response
+}
+
+async fn stream_events(
+    stream: TcpStream,
+    config: &Config,
+    events: &Events,
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    let mut socket =
+        tokio_tungstenite::accept_hdr_async(stream, |request: &Request, response: Response| {
+            if request.uri().path() != EVENTS_PATH {
+                return Err(reject(StatusCode::NOT_FOUND));
+            }
+            let authorization = request
+                .headers()
+                .get("Authorization")
+                .and_then(|value| value.to_str().ok());
+            match access::check(config, authorization, Role::Viewer) {
+                Ok(()) => Ok(response),
+                Err(status) => Err(reject(
+                    StatusCode::from_u16(status.code).unwrap_or(StatusCode::FORBIDDEN),
+                )),
+            }
+        })
+        .await?;
+
+    let mut receiver = events.subscribe();
+    loop {
+        let event = match receiver.recv().await {
+            Ok(event) => event,
+            // Slow observers miss events rather than holding up the plugin
+            Err(RecvError::Lagged(skipped)) => {
+                events.record_dropped(skipped);
+                continue;
+            }
+            Err(RecvError::Closed) => return Ok(()),
+        };
+        let event = serde_json::to_string(&event).unwrap_or_default();
+        socket.send(Message::Text(event)).await?;
+    }
+}
+
+async fn serve(address: SocketAddr, config: LiveConfig, events: Events) -> std::io::Result<()> {
+    let listener = TcpListener::bind(address).await?;
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let config = config.current();
+        let events = events.clone();
+        rocket::tokio::spawn(async move {
+            if let Err(e) = stream_events(stream, &config, &events).await {
+                info!("Event stream closed: {}", e);
+            }
+        });
+    }
+}
+
+pub fn attach(base: Rocket<Build>) -> Rocket<Build> {
+    base.attach(AdHoc::on_liftoff("WebSocket events", |rocket| {
+        Box::pin(async move {
+            let config = match rocket.state::<LiveConfig>() {
+                Some(config) => config.clone(),
+                None => return,
+            };
+            let events = match rocket.state::<Events>() {
+                Some(events) => events.clone(),
+                None => return,
+            };
+
+            if let Some(address) = config.current().events_address() {
+                info!("Streaming events over WebSocket on {}", address);
+                rocket::tokio::spawn(async move {
+                    if let Err(e) = serve(address, config, events).await {
+                        error!("WebSocket listener failed: {}", e);
+                    }
+                });
+            }
+        })
+    }))
+}